@@ -0,0 +1,5 @@
+CREATE TABLE calendars(
+    id VARCHAR(36) PRIMARY KEY DEFAULT gen_random_uuid()::text,
+    summary TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+)
@@ -0,0 +1,8 @@
+CREATE TABLE events(
+    id VARCHAR(36) PRIMARY KEY DEFAULT gen_random_uuid()::text,
+    calendar_id VARCHAR(36) NOT NULL REFERENCES calendars(id) ON DELETE CASCADE,
+    summary TEXT,
+    start_date DATE NOT NULL,
+    end_date DATE NOT NULL,
+    recurrence TEXT[] NOT NULL DEFAULT '{}'
+)
@@ -0,0 +1,4 @@
+CREATE TABLE guild_calendars(
+    guild_id VARCHAR(20) PRIMARY KEY,
+    calendar_id TEXT NOT NULL
+)
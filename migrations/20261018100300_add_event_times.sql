@@ -0,0 +1,7 @@
+ALTER TABLE events
+    ALTER COLUMN start_date DROP NOT NULL,
+    ALTER COLUMN end_date DROP NOT NULL,
+    ADD COLUMN start_date_time TIMESTAMPTZ,
+    ADD COLUMN end_date_time TIMESTAMPTZ,
+    ADD COLUMN time_zone TEXT,
+    ADD CONSTRAINT events_start_check CHECK (start_date IS NOT NULL OR start_date_time IS NOT NULL)
@@ -0,0 +1,4 @@
+CREATE TABLE guild_settings(
+    guild_id VARCHAR(20) PRIMARY KEY,
+    time_zone TEXT NOT NULL DEFAULT 'UTC'
+)
@@ -0,0 +1,8 @@
+CREATE TABLE sent_notifications(
+    guild_id VARCHAR(20) NOT NULL,
+    event_id TEXT NOT NULL,
+    occurrence_date DATE NOT NULL,
+    kind TEXT NOT NULL,
+    sent_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (guild_id, event_id, occurrence_date, kind)
+)
@@ -0,0 +1,5 @@
+ALTER TABLE guild_settings
+    ADD COLUMN reminder_days INTEGER[] NOT NULL DEFAULT '{0}';
+
+ALTER TABLE events
+    ADD COLUMN properties JSONB NOT NULL DEFAULT '{}'
@@ -0,0 +1,12 @@
+CREATE TABLE google_sync_tokens(
+    calendar_id TEXT PRIMARY KEY,
+    sync_token TEXT NOT NULL,
+    synced_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE google_events(
+    calendar_id TEXT NOT NULL,
+    event_id TEXT NOT NULL,
+    event JSONB NOT NULL,
+    PRIMARY KEY (calendar_id, event_id)
+)
@@ -0,0 +1,2 @@
+ALTER TABLE guild_settings
+    ADD COLUMN notification_style TEXT NOT NULL DEFAULT 'embed'
@@ -0,0 +1,8 @@
+CREATE TABLE notification_templates(
+    guild_id VARCHAR(20) NOT NULL,
+    kind TEXT NOT NULL,
+    -- Empty for the server template used by the events of any category
+    category TEXT NOT NULL DEFAULT '',
+    template TEXT NOT NULL,
+    PRIMARY KEY (guild_id, kind, category)
+)
@@ -0,0 +1,2 @@
+ALTER TABLE guild_settings
+    ADD COLUMN date_format TEXT NOT NULL DEFAULT 'iso'
@@ -0,0 +1,3 @@
+ALTER TABLE events
+    ADD COLUMN location TEXT,
+    ADD COLUMN url TEXT
@@ -0,0 +1,7 @@
+CREATE TABLE calendar_adoption(
+    adopted_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- The servers already mapped had their calendars adopted
+INSERT INTO calendar_adoption
+SELECT now() WHERE EXISTS (SELECT 1 FROM guild_calendars);
@@ -0,0 +1,27 @@
+#!/usr/bin/env bash
+set -x
+set -eo pipefail
+
+if ! [ -x "$(command -v curl)" ]
+then
+    echo >&2 "Error: curl is not installed."
+    exit 1
+fi
+
+RADICALE_USER=${RADICALE_USER:=discalen}
+RADICALE_PORT="${RADICALE_PORT:=5232}"
+
+if [ -z "${SKIP_DOCKER}" ]
+then
+docker run \
+    -p 127.0.0.1:"${RADICALE_PORT}":5232 \
+    -d tomsquest/docker-radicale
+fi
+
+until curl -s -o /dev/null "http://localhost:${RADICALE_PORT}/.web/"; do
+    >&2 echo "Radicale is still unavailable - sleeping"
+    sleep 1
+done
+
+>&2 echo "Radicale is up and running on port ${RADICALE_PORT}!"
+>&2 echo "Use calendar.caldav.url = \"http://localhost:${RADICALE_PORT}/${RADICALE_USER}/\""
@@ -0,0 +1,273 @@
+use google_calendar3::api::{Calendar, CalendarListEntry, Event};
+use reqwest::{Method, RequestBuilder, StatusCode};
+use roxmltree::{Document, Node};
+use secrecy::{ExposeSecret, Secret};
+use serenity::async_trait;
+use tracing::{instrument, warn};
+use url::Url;
+use uuid::Uuid;
+
+use crate::{config::CalDavConfig, Error};
+
+use super::CalendarBackend;
+
+mod ical;
+
+const DAV_NS: &str = "DAV:";
+const CALDAV_NS: &str = "urn:ietf:params:xml:ns:caldav";
+
+const PROPFIND_CALENDARS: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:">
+  <d:prop>
+    <d:displayname/>
+    <d:resourcetype/>
+  </d:prop>
+</d:propfind>"#;
+
+const REPORT_EVENTS: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<c:calendar-query xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+  <d:prop>
+    <c:calendar-data/>
+  </d:prop>
+  <c:filter>
+    <c:comp-filter name="VCALENDAR">
+      <c:comp-filter name="VEVENT"/>
+    </c:comp-filter>
+  </c:filter>
+</c:calendar-query>"#;
+
+/// Keeps every guild calendar as a collection under the configured CalDAV home,
+/// e.g. Nextcloud or Radicale.
+///
+/// Calendar ids are the collection urls and event ids are the object names inside them.
+#[derive(Clone, Debug)]
+pub struct CalDavBackend {
+    http: reqwest::Client,
+    url: Url,
+    username: Option<String>,
+    password: Option<Secret<String>>,
+}
+
+impl CalDavBackend {
+    pub fn new(config: &CalDavConfig) -> Result<Self, Error> {
+        Ok(Self {
+            http: reqwest::Client::new(),
+            url: collection_url(Url::parse(&config.url)?),
+            username: config.username.clone(),
+            password: config.password.clone(),
+        })
+    }
+
+    fn request(&self, method: &str, url: Url) -> RequestBuilder {
+        let method = Method::from_bytes(method.as_bytes()).expect("Invalid method");
+        let request = self.http.request(method, url);
+        match &self.username {
+            Some(username) => request.basic_auth(
+                username,
+                self.password
+                    .as_ref()
+                    .map(|password| password.expose_secret()),
+            ),
+            None => request,
+        }
+    }
+
+    async fn xml_request(
+        &self,
+        method: &str,
+        url: Url,
+        body: &'static str,
+    ) -> Result<String, Error> {
+        Ok(self
+            .request(method, url)
+            .header("Depth", "1")
+            .header("Content-Type", "application/xml; charset=utf-8")
+            .body(body)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?)
+    }
+
+    fn event_url(&self, id: &str, calendar_id: &str) -> Result<Url, Error> {
+        Ok(collection_url(Url::parse(calendar_id)?).join(id)?)
+    }
+}
+
+#[async_trait]
+impl CalendarBackend for CalDavBackend {
+    #[instrument(skip(self))]
+    async fn create_calendar(&self, name: &str) -> Result<Calendar, Error> {
+        let url = self.url.join(&format!("{}/", Uuid::new_v4()))?;
+        let body = format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<c:mkcalendar xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+  <d:set>
+    <d:prop>
+      <d:displayname>{}</d:displayname>
+    </d:prop>
+  </d:set>
+</c:mkcalendar>"#,
+            escape_xml(name)
+        );
+        self.request("MKCALENDAR", url.clone())
+            .header("Content-Type", "application/xml; charset=utf-8")
+            .body(body)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(Calendar {
+            id: Some(url.into()),
+            summary: Some(name.to_string()),
+            ..Default::default()
+        })
+    }
+
+    #[instrument(skip(self))]
+    async fn delete_calendar(&self, calendar_id: &str) -> Result<(), Error> {
+        self.request("DELETE", Url::parse(calendar_id)?)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn create_event(&self, event: Event, calendar_id: &str) -> Result<Event, Error> {
+        let uid = Uuid::new_v4().to_string();
+        let id = format!("{uid}.ics");
+        self.request("PUT", self.event_url(&id, calendar_id)?)
+            .header("Content-Type", "text/calendar; charset=utf-8")
+            .header("If-None-Match", "*")
+            .body(ical::to_ics(&uid, &event))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(Event {
+            id: Some(id),
+            i_cal_uid: Some(uid),
+            ..event
+        })
+    }
+
+    #[instrument(skip(self))]
+    async fn update_event(&self, event: Event, calendar_id: &str) -> Result<Event, Error> {
+        let id = event.id.as_deref().ok_or(Error::MissingEventId)?;
+        // The resources created by the bot are named after their UID
+        let uid = match event.i_cal_uid.as_deref() {
+            Some(uid) => uid,
+            None => id.strip_suffix(".ics").unwrap_or(id),
+        };
+        let response = self
+            .request("PUT", self.event_url(id, calendar_id)?)
+            .header("Content-Type", "text/calendar; charset=utf-8")
+            .header("If-Match", "*")
+            .body(ical::to_ics(uid, &event))
+            .send()
+            .await?;
+        if response.status() == StatusCode::PRECONDITION_FAILED {
+            return Err(Error::EventNotFound(id.into()));
+        }
+        response.error_for_status()?;
+        Ok(event)
+    }
+
+    #[instrument(skip(self))]
+    async fn delete_event(&self, id: &str, calendar_id: &str) -> Result<(), Error> {
+        self.request("DELETE", self.event_url(id, calendar_id)?)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn list_calendars(&self) -> Result<Vec<CalendarListEntry>, Error> {
+        let response = self
+            .xml_request("PROPFIND", self.url.clone(), PROPFIND_CALENDARS)
+            .await?;
+        let document = Document::parse(&response)?;
+        let mut calendars = vec![];
+        for response in dav_responses(&document) {
+            let is_calendar = response
+                .descendants()
+                .find(|node| node.has_tag_name((DAV_NS, "resourcetype")))
+                .is_some_and(|resource_type| {
+                    resource_type
+                        .children()
+                        .any(|node| node.has_tag_name((CALDAV_NS, "calendar")))
+                });
+            if !is_calendar {
+                continue;
+            }
+            let Some(href) = element_text(&response, DAV_NS, "href") else {
+                continue;
+            };
+            calendars.push(CalendarListEntry {
+                id: Some(collection_url(self.url.join(href)?).into()),
+                summary: element_text(&response, DAV_NS, "displayname").map(Into::into),
+                ..Default::default()
+            });
+        }
+        Ok(calendars)
+    }
+
+    #[instrument(skip(self))]
+    async fn list_events(&self, calendar_id: &str) -> Result<Vec<Event>, Error> {
+        let url = collection_url(Url::parse(calendar_id)?);
+        let response = self.xml_request("REPORT", url, REPORT_EVENTS).await?;
+        let document = Document::parse(&response)?;
+        let mut events = vec![];
+        for response in dav_responses(&document) {
+            let (Some(href), Some(data)) = (
+                element_text(&response, DAV_NS, "href"),
+                element_text(&response, CALDAV_NS, "calendar-data"),
+            ) else {
+                continue;
+            };
+            let id = href.rsplit('/').next().unwrap_or(href);
+            match ical::from_ics(data) {
+                Ok(event) => events.push(Event {
+                    id: Some(id.to_string()),
+                    ..event
+                }),
+                Err(why) => warn!(?why, id, "Failed to parse the event, skipping..."),
+            }
+        }
+        Ok(events)
+    }
+
+    fn calendar_url(&self, calendar_id: &str) -> Option<String> {
+        Some(calendar_id.to_string())
+    }
+}
+
+fn dav_responses<'a, 'input>(
+    document: &'a Document<'input>,
+) -> impl Iterator<Item = Node<'a, 'input>> {
+    document
+        .descendants()
+        .filter(|node| node.has_tag_name((DAV_NS, "response")))
+}
+
+fn element_text<'a>(node: &Node<'a, '_>, namespace: &str, name: &str) -> Option<&'a str> {
+    node.descendants()
+        .find(|node| node.has_tag_name((namespace, name)))
+        .and_then(|node| node.text())
+        .map(str::trim)
+}
+
+/// Collection urls have to end with a slash to resolve their members.
+fn collection_url(mut url: Url) -> Url {
+    if !url.path().ends_with('/') {
+        url.set_path(&format!("{}/", url.path()));
+    }
+    url
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
@@ -0,0 +1,367 @@
+use chrono::{NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use google_calendar3::api::{Event, EventDateTime, EventSource};
+
+use crate::{
+    calendar::{event_url, set_event_property},
+    Error,
+};
+
+const DATE_FORMAT: &str = "%Y%m%d";
+const DATE_TIME_FORMAT: &str = "%Y%m%dT%H%M%S";
+/// The private extended properties are kept as `X-DISCALEN-<KEY>` properties.
+const PROPERTY_PREFIX: &str = "X-DISCALEN-";
+
+type Params = Vec<(String, String)>;
+
+/// Serializes the event into an iCalendar object with a single VEVENT.
+pub fn to_ics(uid: &str, event: &Event) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".into(),
+        "PRODID:-//discalen//discalen//EN".into(),
+        "BEGIN:VEVENT".into(),
+        format!("UID:{uid}"),
+        format!("DTSTAMP:{}Z", Utc::now().format(DATE_TIME_FORMAT)),
+    ];
+    if let Some(summary) = &event.summary {
+        lines.push(format!("SUMMARY:{}", escape_text(summary)));
+    }
+    if let Some(description) = &event.description {
+        lines.push(format!("DESCRIPTION:{}", escape_text(description)));
+    }
+    if let Some(location) = &event.location {
+        lines.push(format!("LOCATION:{}", escape_text(location)));
+    }
+    if let Some(url) = event_url(event) {
+        lines.push(format!("URL:{url}"));
+    }
+    if let Some(start) = &event.start {
+        lines.extend(date_time_line("DTSTART", start));
+    }
+    if let Some(end) = &event.end {
+        lines.extend(date_time_line("DTEND", end));
+    }
+    // Google keeps the recurrence as raw RRULE/EXDATE/RDATE property lines
+    lines.extend(event.recurrence.iter().flatten().cloned());
+    let properties = event
+        .extended_properties
+        .iter()
+        .flat_map(|properties| properties.private.iter().flatten());
+    for (key, value) in properties {
+        let name = key.to_ascii_uppercase().replace('_', "-");
+        lines.push(format!("{PROPERTY_PREFIX}{name}:{}", escape_text(value)));
+    }
+    lines.push("END:VEVENT".into());
+    lines.push("END:VCALENDAR".into());
+
+    lines
+        .iter()
+        .map(|line| fold(line))
+        .collect::<Vec<_>>()
+        .join("\r\n")
+        + "\r\n"
+}
+
+/// Parses the first VEVENT of an iCalendar object, the event id isn't set but its UID is.
+pub fn from_ics(data: &str) -> Result<Event, Error> {
+    let mut event = Event::default();
+    let mut in_event = false;
+    let mut found = false;
+    for line in unfold(data) {
+        let Some((name, params, value)) = split_property(&line) else {
+            continue;
+        };
+        match (name.as_str(), in_event) {
+            ("BEGIN", false) if value == "VEVENT" => in_event = !found,
+            ("END", true) if value == "VEVENT" => {
+                in_event = false;
+                found = true;
+            }
+            ("UID", true) => event.i_cal_uid = Some(value.into()),
+            ("SUMMARY", true) => event.summary = Some(unescape_text(value)),
+            ("DESCRIPTION", true) => event.description = Some(unescape_text(value)),
+            ("LOCATION", true) => event.location = Some(unescape_text(value)),
+            ("URL", true) => {
+                event.source = Some(EventSource {
+                    url: Some(value.into()),
+                    ..Default::default()
+                })
+            }
+            ("DTSTART", true) => event.start = Some(parse_date_time(&params, value)?),
+            ("DTEND", true) => event.end = Some(parse_date_time(&params, value)?),
+            ("RRULE" | "RDATE" | "EXDATE", true) => event
+                .recurrence
+                .get_or_insert_with(Vec::new)
+                .push(line.clone()),
+            (name, true) if name.starts_with(PROPERTY_PREFIX) => {
+                let key = name[PROPERTY_PREFIX.len()..]
+                    .to_ascii_lowercase()
+                    .replace('-', "_");
+                set_event_property(&mut event, &key, unescape_text(value));
+            }
+            _ => (),
+        }
+    }
+    if !found {
+        return Err(Error::InvalidICalendar("no VEVENT found".into()));
+    }
+    Ok(event)
+}
+
+/// The timed events with a known zone are written in their local time, so their recurrence
+/// keeps the local time across DST changes here and in the other clients.
+fn date_time_line(name: &str, date_time: &EventDateTime) -> Option<String> {
+    let time_zone = date_time
+        .time_zone
+        .as_deref()
+        .and_then(|time_zone| time_zone.parse::<Tz>().ok())
+        .filter(|time_zone| *time_zone != Tz::UTC);
+    match (date_time.date, date_time.date_time, time_zone) {
+        (_, Some(date_time), Some(time_zone)) => Some(format!(
+            "{name};TZID={}:{}",
+            time_zone.name(),
+            date_time
+                .with_timezone(&time_zone)
+                .naive_local()
+                .format(DATE_TIME_FORMAT)
+        )),
+        (_, Some(date_time), None) => Some(format!(
+            "{name}:{}Z",
+            date_time.naive_utc().format(DATE_TIME_FORMAT)
+        )),
+        (Some(date), None, _) => Some(format!("{name};VALUE=DATE:{}", date.format(DATE_FORMAT))),
+        (None, None, _) => None,
+    }
+}
+
+fn parse_date_time(params: &Params, value: &str) -> Result<EventDateTime, Error> {
+    let invalid = || Error::InvalidICalendar(format!("invalid date {value}"));
+    let is_date = params
+        .iter()
+        .any(|(name, value)| name == "VALUE" && value == "DATE");
+    if is_date || value.len() == 8 {
+        let date = NaiveDate::parse_from_str(value, DATE_FORMAT).map_err(|_| invalid())?;
+        return Ok(EventDateTime {
+            date: Some(date),
+            ..Default::default()
+        });
+    }
+    let date_time = NaiveDateTime::parse_from_str(value.trim_end_matches('Z'), DATE_TIME_FORMAT)
+        .map_err(|_| invalid())?;
+    let time_zone = params
+        .iter()
+        .find(|(name, _)| name == "TZID")
+        .and_then(|(_, value)| value.parse::<Tz>().ok());
+    // Floating times and unknown zones are read as UTC
+    let date_time = match time_zone {
+        Some(time_zone) => time_zone
+            .from_local_datetime(&date_time)
+            .earliest()
+            .ok_or_else(invalid)?
+            .with_timezone(&Utc),
+        None => date_time.and_utc(),
+    };
+    Ok(EventDateTime {
+        date_time: Some(date_time),
+        time_zone: time_zone.map(|time_zone| time_zone.name().into()),
+        ..Default::default()
+    })
+}
+
+/// Splits a content line into its name, parameters and value.
+fn split_property(line: &str) -> Option<(String, Params, &str)> {
+    let (head, value) = line.split_once(':')?;
+    let mut parts = head.split(';');
+    let name = parts.next()?.to_ascii_uppercase();
+    let params = parts
+        .filter_map(|param| {
+            let (name, value) = param.split_once('=')?;
+            Some((
+                name.to_ascii_uppercase(),
+                value.trim_matches('"').to_string(),
+            ))
+        })
+        .collect();
+    Some((name, params, value))
+}
+
+fn unfold(data: &str) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    for line in data.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+fn fold(line: &str) -> String {
+    const MAX_LINE_LENGTH: usize = 74;
+    let mut folded = String::with_capacity(line.len());
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > MAX_LINE_LENGTH {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+fn unescape_text(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => unescaped.push('\n'),
+            Some(c) => unescaped.push(c),
+            None => (),
+        }
+    }
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, NaiveDate, TimeZone, Utc};
+    use google_calendar3::api::{Event, EventDateTime};
+
+    use super::{from_ics, to_ics};
+    use crate::calendar::{
+        get_event_property, occurrences_between, set_event_property, EventStart,
+    };
+
+    #[test]
+    fn round_trips_an_all_day_event() {
+        let mut event = Event {
+            summary: Some("Alice's birthday; party, cake".into()),
+            description: Some("Bring\na gift".into()),
+            start: Some(EventDateTime {
+                date: NaiveDate::from_ymd_opt(2026, 12, 25),
+                ..Default::default()
+            }),
+            end: Some(EventDateTime {
+                date: NaiveDate::from_ymd_opt(2026, 12, 26),
+                ..Default::default()
+            }),
+            recurrence: Some(vec!["RRULE:FREQ=YEARLY".into()]),
+            ..Default::default()
+        };
+        set_event_property(&mut event, "reminder_days", "7,1,0".into());
+
+        let parsed = from_ics(&to_ics("uid-1", &event)).unwrap();
+        assert_eq!(parsed.i_cal_uid.as_deref(), Some("uid-1"));
+        assert_eq!(parsed.summary, event.summary);
+        assert_eq!(parsed.description, event.description);
+        assert_eq!(
+            parsed.start.as_ref().unwrap().date,
+            NaiveDate::from_ymd_opt(2026, 12, 25)
+        );
+        assert_eq!(
+            parsed.end.as_ref().unwrap().date,
+            NaiveDate::from_ymd_opt(2026, 12, 26)
+        );
+        assert_eq!(parsed.recurrence, event.recurrence);
+        assert_eq!(get_event_property(&parsed, "reminder_days"), Some("7,1,0"));
+    }
+
+    #[test]
+    fn round_trips_a_timed_event_as_utc() {
+        let start = Utc.with_ymd_and_hms(2026, 12, 25, 18, 30, 0).unwrap();
+        let event = Event {
+            summary: Some("Dinner".into()),
+            start: Some(EventDateTime {
+                date_time: Some(start),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let parsed = from_ics(&to_ics("uid-2", &event)).unwrap();
+        assert_eq!(parsed.start.unwrap().date_time, Some(start));
+    }
+
+    #[test]
+    fn round_trips_a_timed_event_in_its_zone() {
+        // 19:00 in Berlin, before the DST switch of 2027-03-28
+        let start = Utc.with_ymd_and_hms(2027, 3, 19, 18, 0, 0).unwrap();
+        let event = Event {
+            start: Some(EventDateTime {
+                date_time: Some(start),
+                time_zone: Some("Europe/Berlin".into()),
+                ..Default::default()
+            }),
+            end: Some(EventDateTime {
+                date_time: Some(start + Duration::hours(2)),
+                time_zone: Some("Europe/Berlin".into()),
+                ..Default::default()
+            }),
+            recurrence: Some(vec!["RRULE:FREQ=WEEKLY".into()]),
+            ..Default::default()
+        };
+
+        let ics = to_ics("uid-5", &event);
+        assert!(ics.contains("DTSTART;TZID=Europe/Berlin:20270319T190000\r\n"));
+        assert!(ics.contains("DTEND;TZID=Europe/Berlin:20270319T210000\r\n"));
+        let parsed = from_ics(&ics).unwrap();
+        let parsed_start = parsed.start.as_ref().unwrap();
+        assert_eq!(parsed_start.date_time, Some(start));
+        assert_eq!(parsed_start.time_zone.as_deref(), Some("Europe/Berlin"));
+
+        // Still at 19:00 in Berlin after the switch
+        let from = Utc.with_ymd_and_hms(2027, 3, 30, 0, 0, 0).unwrap();
+        let occurrences = occurrences_between(&parsed, from, from + Duration::days(7)).unwrap();
+        assert_eq!(
+            occurrences,
+            vec![EventStart::Timed(
+                Utc.with_ymd_and_hms(2027, 4, 2, 17, 0, 0).unwrap()
+            )]
+        );
+    }
+
+    #[test]
+    fn reads_tzid_times_in_their_zone() {
+        let data = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nUID:uid-3\r\n\
+            DTSTART;TZID=Europe/Berlin:20261225T183000\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+
+        let start = from_ics(data).unwrap().start.unwrap();
+        assert_eq!(
+            start.date_time,
+            Some(Utc.with_ymd_and_hms(2026, 12, 25, 17, 30, 0).unwrap())
+        );
+        assert_eq!(start.time_zone.as_deref(), Some("Europe/Berlin"));
+    }
+
+    #[test]
+    fn unfolds_long_lines() {
+        let summary = "A very long summary ".repeat(10);
+        let event = Event {
+            summary: Some(summary.clone()),
+            ..Default::default()
+        };
+
+        let ics = to_ics("uid-4", &event);
+        assert!(ics.lines().all(|line| line.len() <= 75));
+        assert_eq!(from_ics(&ics).unwrap().summary, Some(summary));
+    }
+
+    #[test]
+    fn rejects_data_without_an_event() {
+        assert!(from_ics("BEGIN:VCALENDAR\r\nEND:VCALENDAR\r\n").is_err());
+    }
+}
@@ -0,0 +1,392 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use futures::{Stream, TryStreamExt};
+use google_calendar3::{
+    api::{AclRule, AclRuleScope, Calendar, CalendarListEntry, Event},
+    hyper, hyper_rustls, CalendarHub,
+};
+use serenity::async_trait;
+use sqlx::PgPool;
+use tracing::{info, instrument};
+use uuid::Uuid;
+use yup_oauth2::{
+    hyper::Client as CalendarClient, parse_service_account_key, ServiceAccountAuthenticator,
+};
+
+use crate::{config::GoogleConfig, Error};
+
+use super::CalendarBackend;
+
+mod mirror;
+use mirror::*;
+mod retry;
+use retry::*;
+
+/// The most events Google returns in a single page.
+const EVENTS_PAGE_SIZE: i32 = 2500;
+
+pub type MyCalendarHub =
+    CalendarHub<hyper_rustls::HttpsConnector<hyper::client::connect::HttpConnector>>;
+
+pub async fn authenticate_calendar_hub(key: impl AsRef<[u8]>) -> Result<MyCalendarHub, Error> {
+    let sa_key = parse_service_account_key(key)?;
+    let auth = ServiceAccountAuthenticator::builder(sa_key).build().await?;
+
+    let https_connector = hyper_rustls::HttpsConnectorBuilder::new()
+        .with_native_roots()
+        .https_or_http()
+        .enable_http1()
+        .build();
+    let client = CalendarClient::builder().build(https_connector);
+
+    Ok(CalendarHub::new(client, auth))
+}
+
+/// Google Calendar backend, the events are read from a local mirror kept up to date with sync
+/// tokens, so only the changes are fetched on every read. The reads of a date range go to Google
+/// directly.
+#[derive(Clone)]
+pub struct Client {
+    pub calendar_hub: MyCalendarHub,
+    pub pool: PgPool,
+    requests: Arc<RequestPolicy>,
+}
+
+impl Client {
+    pub async fn with_sa_key(
+        key: impl AsRef<[u8]>,
+        pool: PgPool,
+        config: &GoogleConfig,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            calendar_hub: authenticate_calendar_hub(key.as_ref()).await?,
+            pool,
+            requests: Arc::new(RequestPolicy::new(config)),
+        })
+    }
+
+    /// Streams the calendars of the service account, following the pages to the end.
+    pub fn calendar_list_stream(
+        &self,
+    ) -> impl Stream<Item = Result<CalendarListEntry, Error>> + '_ {
+        // `None` once the last page is read, `Some(None)` for the first page
+        futures::stream::try_unfold(
+            Some(None),
+            move |page_token: Option<Option<String>>| async move {
+                let Some(page_token) = page_token else {
+                    return Ok(None);
+                };
+                let calendars = self
+                    .requests
+                    .execute(true, |mut retry_after| {
+                        let page_token = page_token.clone();
+                        async move {
+                            let mut call = self
+                                .calendar_hub
+                                .calendar_list()
+                                .list()
+                                .delegate(&mut retry_after);
+                            if let Some(page_token) = page_token.as_deref() {
+                                call = call.page_token(page_token);
+                            }
+                            call.doit().await
+                        }
+                    })
+                    .await?
+                    .1;
+                let entries = calendars.items.unwrap_or_default().into_iter().map(Ok);
+                let next_page_token = calendars.next_page_token.map(Some);
+                Ok::<_, Error>(Some((futures::stream::iter(entries), next_page_token)))
+            },
+        )
+        .try_flatten()
+    }
+
+    /// Brings the mirror of the calendar up to date with Google.
+    #[instrument(skip(self))]
+    pub async fn sync_events(&self, calendar_id: &str) -> Result<(), Error> {
+        let sync_token = get_sync_token(&self.pool, calendar_id).await?;
+        let changes = match self
+            .fetch_event_changes(calendar_id, sync_token.as_deref())
+            .await
+        {
+            Err(Error::GoogleError(why)) if sync_token.is_some() && is_sync_token_expired(&why) => {
+                info!("The sync token has expired, syncing the calendar from scratch");
+                self.fetch_event_changes(calendar_id, None).await?
+            }
+            changes => changes?,
+        };
+        apply_event_changes(&self.pool, calendar_id, changes).await
+    }
+
+    /// Fetches all the pages of the changes since the sync token, or all the events without one.
+    async fn fetch_event_changes(
+        &self,
+        calendar_id: &str,
+        sync_token: Option<&str>,
+    ) -> Result<EventChanges, Error> {
+        let mut changes = EventChanges {
+            full: sync_token.is_none(),
+            ..Default::default()
+        };
+        let mut page_token = None;
+        loop {
+            let events = self
+                .requests
+                .execute(true, |mut retry_after| {
+                    let page_token = page_token.clone();
+                    async move {
+                        let mut call = self
+                            .calendar_hub
+                            .events()
+                            .list(calendar_id)
+                            .max_results(EVENTS_PAGE_SIZE)
+                            .delegate(&mut retry_after);
+                        if let Some(sync_token) = sync_token {
+                            call = call.sync_token(sync_token);
+                        }
+                        if let Some(page_token) = page_token.as_deref() {
+                            call = call.page_token(page_token);
+                        }
+                        call.doit().await
+                    }
+                })
+                .await?
+                .1;
+
+            for event in events.items.unwrap_or_default() {
+                // The cancelled instances of a recurring event are kept to exclude their dates
+                let is_cancelled = event.status.as_deref() == Some("cancelled");
+                if is_cancelled && event.recurring_event_id.is_none() {
+                    changes.deleted.extend(event.id);
+                } else {
+                    changes.updated.push(event);
+                }
+            }
+            match (events.next_page_token, events.next_sync_token) {
+                (Some(next_page_token), _) => page_token = Some(next_page_token),
+                (None, Some(next_sync_token)) => {
+                    changes.next_sync_token = next_sync_token;
+                    return Ok(changes);
+                }
+                (None, None) => return Err(Error::MissingResponseField("next sync token")),
+            }
+        }
+    }
+}
+
+/// Google answers `410 Gone` when the sync token is no longer valid.
+fn is_sync_token_expired(error: &google_calendar3::Error) -> bool {
+    status_code(error) == Some(410)
+}
+
+#[async_trait]
+impl CalendarBackend for Client {
+    #[instrument(skip(self))]
+    async fn create_calendar(&self, name: &str) -> Result<Calendar, Error> {
+        let calendar = Calendar {
+            summary: Some(name.to_string()),
+            ..Default::default()
+        };
+        // Every retry would create another calendar, so only the rejected requests are retried
+        let calendar = self
+            .requests
+            .execute(false, |mut retry_after| {
+                let calendar = calendar.clone();
+                async move {
+                    self.calendar_hub
+                        .calendars()
+                        .insert(calendar)
+                        .delegate(&mut retry_after)
+                        .doit()
+                        .await
+                }
+            })
+            .await?
+            .1;
+
+        let rule = AclRule {
+            role: Some("reader".into()),
+            scope: Some(AclRuleScope {
+                type_: Some("default".into()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let calendar_id = calendar.id.as_deref().ok_or(Error::MissingCalendarId)?;
+        self.requests
+            .execute(true, |mut retry_after| {
+                let rule = rule.clone();
+                async move {
+                    self.calendar_hub
+                        .acl()
+                        .insert(rule, calendar_id)
+                        .delegate(&mut retry_after)
+                        .doit()
+                        .await
+                }
+            })
+            .await?;
+        Ok(calendar)
+    }
+
+    #[instrument(skip(self))]
+    async fn delete_calendar(&self, calendar_id: &str) -> Result<(), Error> {
+        self.requests
+            .execute(true, |mut retry_after| async move {
+                self.calendar_hub
+                    .calendars()
+                    .delete(calendar_id)
+                    .delegate(&mut retry_after)
+                    .doit()
+                    .await
+            })
+            .await?;
+        delete_mirror(&self.pool, calendar_id).await?;
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn create_event(&self, mut event: Event, calendar_id: &str) -> Result<Event, Error> {
+        // With an id of our own a retried insert can't create the event twice
+        let event_id = event
+            .id
+            .get_or_insert_with(|| Uuid::new_v4().simple().to_string())
+            .clone();
+        let response = self
+            .requests
+            .execute(true, |mut retry_after| {
+                let event = event.clone();
+                async move {
+                    self.calendar_hub
+                        .events()
+                        .insert(event, calendar_id)
+                        .delegate(&mut retry_after)
+                        .doit()
+                        .await
+                }
+            })
+            .await;
+        match response {
+            Ok((_, event)) => Ok(event),
+            // An earlier attempt has created the event, but its response was lost
+            Err(Error::GoogleError(why)) if status_code(&why) == Some(409) => Ok(self
+                .requests
+                .execute(true, |mut retry_after| {
+                    let event_id = event_id.clone();
+                    async move {
+                        self.calendar_hub
+                            .events()
+                            .get(calendar_id, &event_id)
+                            .delegate(&mut retry_after)
+                            .doit()
+                            .await
+                    }
+                })
+                .await?
+                .1),
+            Err(why) => Err(why),
+        }
+    }
+
+    #[instrument(skip(self))]
+    async fn update_event(&self, event: Event, calendar_id: &str) -> Result<Event, Error> {
+        let event_id = event.id.clone().ok_or(Error::MissingEventId)?;
+        let event = self
+            .requests
+            .execute(true, |mut retry_after| {
+                let event = event.clone();
+                let event_id = event_id.clone();
+                async move {
+                    self.calendar_hub
+                        .events()
+                        .update(event, calendar_id, &event_id)
+                        .delegate(&mut retry_after)
+                        .doit()
+                        .await
+                }
+            })
+            .await?
+            .1;
+        Ok(event)
+    }
+
+    #[instrument(skip(self))]
+    async fn delete_event(&self, id: &str, calendar_id: &str) -> Result<(), Error> {
+        self.requests
+            .execute(true, |mut retry_after| async move {
+                self.calendar_hub
+                    .events()
+                    .delete(calendar_id, id)
+                    .delegate(&mut retry_after)
+                    .doit()
+                    .await
+            })
+            .await?;
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn list_calendars(&self) -> Result<Vec<CalendarListEntry>, Error> {
+        self.calendar_list_stream().try_collect().await
+    }
+
+    #[instrument(skip(self))]
+    async fn list_events(&self, calendar_id: &str) -> Result<Vec<Event>, Error> {
+        self.sync_events(calendar_id).await?;
+        list_mirrored_events(&self.pool, calendar_id).await
+    }
+
+    /// Asks Google for the events within the range only, a recurring event comes once if any
+    /// of its occurrences is within it.
+    #[instrument(skip(self))]
+    async fn list_events_between(
+        &self,
+        calendar_id: &str,
+        from: DateTime<Utc>,
+        until: Option<DateTime<Utc>>,
+    ) -> Result<Vec<Event>, Error> {
+        let mut events = vec![];
+        let mut page_token = None;
+        loop {
+            let page = self
+                .requests
+                .execute(true, |mut retry_after| {
+                    let page_token = page_token.clone();
+                    async move {
+                        let mut call = self
+                            .calendar_hub
+                            .events()
+                            .list(calendar_id)
+                            .time_min(from)
+                            .max_results(EVENTS_PAGE_SIZE)
+                            .delegate(&mut retry_after);
+                        if let Some(until) = until {
+                            call = call.time_max(until);
+                        }
+                        if let Some(page_token) = page_token.as_deref() {
+                            call = call.page_token(page_token);
+                        }
+                        call.doit().await
+                    }
+                })
+                .await?
+                .1;
+
+            events.extend(page.items.unwrap_or_default());
+            match page.next_page_token {
+                Some(next_page_token) => page_token = Some(next_page_token),
+                None => return Ok(fold_exceptions(events)),
+            }
+        }
+    }
+
+    fn calendar_url(&self, calendar_id: &str) -> Option<String> {
+        Some(get_calendar_url(calendar_id))
+    }
+}
+
+pub fn get_calendar_url(calendar_id: &str) -> String {
+    format!("https://calendar.google.com/calendar/u/0?cid={calendar_id}")
+}
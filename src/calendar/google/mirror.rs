@@ -0,0 +1,273 @@
+use google_calendar3::api::{Event, EventDateTime};
+use sqlx::{query, types::Json, PgPool};
+
+use crate::Error;
+
+/// The changes of a calendar fetched from Google since the last sync.
+#[derive(Debug, Default)]
+pub struct EventChanges {
+    /// The mirror is rebuilt from scratch, the sync token has expired or never existed.
+    pub full: bool,
+    pub updated: Vec<Event>,
+    pub deleted: Vec<String>,
+    pub next_sync_token: String,
+}
+
+pub async fn get_sync_token(pool: &PgPool, calendar_id: &str) -> Result<Option<String>, Error> {
+    let response = query!(
+        "
+        SELECT sync_token FROM google_sync_tokens
+        WHERE calendar_id = $1
+        ",
+        calendar_id
+    )
+    .fetch_optional(pool)
+    .await?
+    .map(|record| record.sync_token);
+    Ok(response)
+}
+
+/// Applies the changes and stores the new sync token at once, so a failed sync is retried
+/// from the previous token.
+pub async fn apply_event_changes(
+    pool: &PgPool,
+    calendar_id: &str,
+    changes: EventChanges,
+) -> Result<(), Error> {
+    let mut transaction = pool.begin().await?;
+    if changes.full {
+        query!(
+            "
+            DELETE FROM google_events
+            WHERE calendar_id = $1
+            ",
+            calendar_id
+        )
+        .execute(&mut *transaction)
+        .await?;
+    }
+    for event in changes.updated {
+        let Some(event_id) = event.id.clone() else {
+            continue;
+        };
+        query!(
+            "
+            INSERT INTO google_events(calendar_id, event_id, event)
+            VALUES($1, $2, $3)
+            ON CONFLICT (calendar_id, event_id) DO UPDATE SET event = EXCLUDED.event
+            ",
+            calendar_id,
+            event_id,
+            Json(event) as _,
+        )
+        .execute(&mut *transaction)
+        .await?;
+    }
+    query!(
+        "
+        DELETE FROM google_events
+        WHERE calendar_id = $1 AND event_id = ANY($2)
+        ",
+        calendar_id,
+        &changes.deleted,
+    )
+    .execute(&mut *transaction)
+    .await?;
+    query!(
+        "
+        INSERT INTO google_sync_tokens(calendar_id, sync_token)
+        VALUES($1, $2)
+        ON CONFLICT (calendar_id) DO UPDATE
+        SET sync_token = EXCLUDED.sync_token, synced_at = now()
+        ",
+        calendar_id,
+        changes.next_sync_token,
+    )
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// The mirrored events, the exceptions of the recurring events folded into them.
+pub async fn list_mirrored_events(pool: &PgPool, calendar_id: &str) -> Result<Vec<Event>, Error> {
+    let events = query!(
+        r#"
+        SELECT event AS "event: Json<Event>" FROM google_events
+        WHERE calendar_id = $1
+        ORDER BY COALESCE(event->'start'->>'dateTime', event->'start'->>'date')
+        "#,
+        calendar_id
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|record| record.event.0)
+    .collect();
+    Ok(fold_exceptions(events))
+}
+
+/// Google lists the changed and the cancelled instances of a recurring event apart from it. Their
+/// original dates are excluded from the recurring event, the changed ones are kept as single
+/// events and the cancelled ones dropped, so every occurrence is announced once.
+pub fn fold_exceptions(events: Vec<Event>) -> Vec<Event> {
+    let (exceptions, mut events): (Vec<_>, Vec<_>) = events
+        .into_iter()
+        .partition(|event| event.recurring_event_id.is_some());
+    for exception in exceptions {
+        let master = events
+            .iter_mut()
+            .find(|event| event.id.is_some() && event.id == exception.recurring_event_id);
+        let exdate = exception.original_start_time.as_ref().and_then(exdate_line);
+        if let (Some(master), Some(exdate)) = (master, exdate) {
+            master.recurrence.get_or_insert_with(Vec::new).push(exdate);
+        }
+        if exception.status.as_deref() != Some("cancelled") {
+            events.push(Event {
+                recurrence: None,
+                ..exception
+            });
+        }
+    }
+    events
+}
+
+fn exdate_line(original_start: &EventDateTime) -> Option<String> {
+    match (original_start.date, original_start.date_time) {
+        (_, Some(date_time)) => Some(format!(
+            "EXDATE:{}",
+            date_time.naive_utc().format("%Y%m%dT%H%M%SZ")
+        )),
+        (Some(date), None) => Some(format!("EXDATE;VALUE=DATE:{}", date.format("%Y%m%d"))),
+        (None, None) => None,
+    }
+}
+
+pub async fn delete_mirror(pool: &PgPool, calendar_id: &str) -> Result<(), Error> {
+    let mut transaction = pool.begin().await?;
+    query!(
+        "
+        DELETE FROM google_events
+        WHERE calendar_id = $1
+        ",
+        calendar_id
+    )
+    .execute(&mut *transaction)
+    .await?;
+    query!(
+        "
+        DELETE FROM google_sync_tokens
+        WHERE calendar_id = $1
+        ",
+        calendar_id
+    )
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, NaiveDate, TimeZone, Utc};
+    use google_calendar3::api::{Event, EventDateTime};
+
+    use super::fold_exceptions;
+    use crate::calendar::{occurrences_between, EventStart};
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 12, day).unwrap()
+    }
+
+    fn all_day(date: NaiveDate) -> Option<EventDateTime> {
+        Some(EventDateTime {
+            date: Some(date),
+            ..Default::default()
+        })
+    }
+
+    /// Every day from December 25.
+    fn master() -> Event {
+        Event {
+            id: Some("master".into()),
+            start: all_day(date(25)),
+            end: all_day(date(26)),
+            recurrence: Some(vec!["RRULE:FREQ=DAILY".into()]),
+            ..Default::default()
+        }
+    }
+
+    fn starts(events: &[Event]) -> Vec<(String, EventStart)> {
+        let from = EventStart::AllDay(date(25)).date_time();
+        events
+            .iter()
+            .flat_map(|event| {
+                occurrences_between(event, from, from + Duration::days(3))
+                    .unwrap()
+                    .into_iter()
+                    .map(|start| (event.id.clone().unwrap(), start))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn excludes_a_cancelled_instance() {
+        let cancelled = Event {
+            id: Some("master_20261226".into()),
+            status: Some("cancelled".into()),
+            recurring_event_id: Some("master".into()),
+            original_start_time: all_day(date(26)),
+            ..Default::default()
+        };
+
+        let events = fold_exceptions(vec![cancelled, master()]);
+        assert_eq!(events.len(), 1);
+        assert_eq!(
+            starts(&events),
+            vec![
+                ("master".into(), EventStart::AllDay(date(25))),
+                ("master".into(), EventStart::AllDay(date(27))),
+                ("master".into(), EventStart::AllDay(date(28))),
+            ]
+        );
+    }
+
+    #[test]
+    fn moves_a_changed_instance() {
+        let start = Utc.with_ymd_and_hms(2026, 12, 27, 18, 0, 0).unwrap();
+        let moved = Event {
+            id: Some("master_20261226".into()),
+            status: Some("confirmed".into()),
+            recurring_event_id: Some("master".into()),
+            original_start_time: all_day(date(26)),
+            start: Some(EventDateTime {
+                date_time: Some(start),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let events = fold_exceptions(vec![master(), moved]);
+        assert_eq!(
+            starts(&events),
+            vec![
+                ("master".into(), EventStart::AllDay(date(25))),
+                ("master".into(), EventStart::AllDay(date(27))),
+                ("master".into(), EventStart::AllDay(date(28))),
+                ("master_20261226".into(), EventStart::Timed(start)),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_a_changed_instance_without_its_event() {
+        let orphan = Event {
+            id: Some("gone_20261226".into()),
+            recurring_event_id: Some("gone".into()),
+            original_start_time: all_day(date(26)),
+            start: all_day(date(26)),
+            ..Default::default()
+        };
+        assert_eq!(fold_exceptions(vec![orphan]).len(), 1);
+    }
+}
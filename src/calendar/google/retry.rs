@@ -0,0 +1,174 @@
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use google_calendar3::{
+    client::Retry,
+    hyper::{self, header::RETRY_AFTER},
+    Delegate,
+};
+use rand::Rng;
+use tokio::time::Instant;
+use tracing::warn;
+
+use crate::{config::GoogleConfig, Error};
+
+/// The delay before the first retry, doubled on every next one.
+const BASE_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(32);
+
+/// Spaces the Google API requests out to the configured rate and retries the transient failures.
+pub struct RequestPolicy {
+    interval: Duration,
+    max_retries: u32,
+    timeout: Duration,
+    next_request: tokio::sync::Mutex<Instant>,
+}
+
+impl RequestPolicy {
+    pub fn new(config: &GoogleConfig) -> Self {
+        let interval = match config.requests_per_second {
+            0 => Duration::ZERO,
+            requests_per_second => Duration::from_secs(1) / requests_per_second,
+        };
+        Self {
+            interval,
+            max_retries: config.max_retries,
+            timeout: config.request_timeout,
+            next_request: tokio::sync::Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Sends the request built by `request`, again on 5xx, 429 and timeouts. A request that isn't
+    /// `idempotent` is only retried when the quota rejected it, it might have been applied
+    /// otherwise.
+    pub async fn execute<T, F, Fut>(&self, idempotent: bool, request: F) -> Result<T, Error>
+    where
+        F: Fn(RetryAfter) -> Fut,
+        Fut: Future<Output = google_calendar3::Result<T>>,
+    {
+        let mut attempt = 0;
+        loop {
+            self.throttle().await;
+            let retry_after = RetryAfter::default();
+            let error = match tokio::time::timeout(self.timeout, request(retry_after.clone())).await
+            {
+                Ok(Ok(response)) => return Ok(response),
+                Ok(Err(why)) if is_rate_limited(&why) || (idempotent && is_transient(&why)) => {
+                    Error::from(why)
+                }
+                Ok(Err(why)) => return Err(why.into()),
+                Err(_) if idempotent => Error::GoogleTimeout,
+                Err(_) => return Err(Error::GoogleTimeout),
+            };
+            if attempt >= self.max_retries {
+                return Err(error);
+            }
+
+            let delay = retry_after.get().unwrap_or_else(|| backoff(attempt));
+            warn!(
+                ?error,
+                attempt,
+                ?delay,
+                "The Google API request failed, retrying..."
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    async fn throttle(&self) {
+        let mut next_request = self.next_request.lock().await;
+        let at = (*next_request).max(Instant::now());
+        *next_request = at + self.interval;
+        drop(next_request);
+        tokio::time::sleep_until(at).await;
+    }
+}
+
+/// Exponential backoff with jitter, so the failed requests don't come back all at once.
+fn backoff(attempt: u32) -> Duration {
+    let backoff = BASE_BACKOFF
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(MAX_BACKOFF);
+    backoff / 2 + rand::thread_rng().gen_range(Duration::ZERO..=backoff / 2)
+}
+
+/// Remembers the `Retry-After` of a failed response, the retry itself is left to the policy.
+#[derive(Clone, Default)]
+pub struct RetryAfter(Arc<Mutex<Option<Duration>>>);
+
+impl RetryAfter {
+    fn get(&self) -> Option<Duration> {
+        *self.0.lock().expect("Poisoned retry after")
+    }
+}
+
+impl Delegate for RetryAfter {
+    fn http_failure(
+        &mut self,
+        response: &hyper::Response<hyper::Body>,
+        _: Option<serde_json::Value>,
+    ) -> Retry {
+        // Either the seconds to wait or the date to retry at
+        let retry_after = response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| match value.parse() {
+                Ok(seconds) => Some(Duration::from_secs(seconds)),
+                Err(_) => (DateTime::parse_from_rfc2822(value)
+                    .ok()?
+                    .with_timezone(&Utc)
+                    - Utc::now())
+                .to_std()
+                .ok(),
+            });
+        *self.0.lock().expect("Poisoned retry after") = retry_after;
+        Retry::Abort
+    }
+}
+
+/// The HTTP status of a failed request, Google reports most of them in the JSON body.
+pub fn status_code(error: &google_calendar3::Error) -> Option<u16> {
+    match error {
+        google_calendar3::Error::BadRequest(value) => {
+            value["error"]["code"].as_u64()?.try_into().ok()
+        }
+        google_calendar3::Error::Failure(response) => Some(response.status().as_u16()),
+        _ => None,
+    }
+}
+
+/// The quota rejected the request, Google answers `403` with a rate limit reason or `429`.
+fn is_rate_limited(error: &google_calendar3::Error) -> bool {
+    match status_code(error) {
+        Some(429) => true,
+        Some(403) => {
+            let google_calendar3::Error::BadRequest(value) = error else {
+                return false;
+            };
+            value["error"]["errors"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .any(|error| {
+                    matches!(
+                        error["reason"].as_str(),
+                        Some("rateLimitExceeded" | "userRateLimitExceeded")
+                    )
+                })
+        }
+        _ => false,
+    }
+}
+
+fn is_transient(error: &google_calendar3::Error) -> bool {
+    match error {
+        google_calendar3::Error::HttpError(_) | google_calendar3::Error::Io(_) => true,
+        error => matches!(status_code(error), Some(500..=599)),
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::{btree_map::Entry, BTreeMap};
+
+use serenity::all::GuildId;
+use sqlx::{query, PgPool};
+use tracing::{info, instrument, warn};
+
+use crate::Error;
+
+use super::CalendarBackend;
+
+pub async fn set_guild_calendar(
+    pool: &PgPool,
+    guild_id: &GuildId,
+    calendar_id: &str,
+) -> Result<(), Error> {
+    query!(
+        "
+        INSERT INTO guild_calendars(guild_id, calendar_id)
+        VALUES($1, $2)
+        ON CONFLICT (guild_id) DO UPDATE SET calendar_id = EXCLUDED.calendar_id
+        ",
+        guild_id.get().to_string(),
+        calendar_id,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_guild_calendar_id(
+    pool: &PgPool,
+    guild_id: &GuildId,
+) -> Result<Option<String>, Error> {
+    let response = query!(
+        "
+        SELECT calendar_id FROM guild_calendars
+        WHERE guild_id = $1
+        ",
+        guild_id.get().to_string()
+    )
+    .fetch_optional(pool)
+    .await?
+    .map(|record| record.calendar_id);
+    Ok(response)
+}
+
+pub async fn delete_guild_calendar(pool: &PgPool, guild_id: &GuildId) -> Result<(), Error> {
+    query!(
+        "
+        DELETE FROM guild_calendars WHERE guild_id = $1
+        ",
+        guild_id.get().to_string()
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn list_guild_calendars(pool: &PgPool) -> Result<Vec<(GuildId, String)>, Error> {
+    let response = query!(
+        "
+        SELECT guild_id, calendar_id FROM guild_calendars
+        "
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .filter_map(|record| {
+        let guild_id = record.guild_id.parse::<u64>().ok()?;
+        Some((GuildId::new(guild_id), record.calendar_id))
+    })
+    .collect();
+    Ok(response)
+}
+
+/// Maps the calendars named after a server id to their servers.
+///
+/// Calendars used to be matched by their summary on every lookup, this runs once to keep them
+/// working. A server with more than one such calendar gets the one with the lowest id, so the
+/// pick doesn't depend on the order the backend lists them in.
+#[instrument(skip_all)]
+pub async fn adopt_guild_calendars(
+    pool: &PgPool,
+    backend: &dyn CalendarBackend,
+) -> Result<(), Error> {
+    let is_adopted = query!(
+        "
+        SELECT adopted_at FROM calendar_adoption LIMIT 1
+        "
+    )
+    .fetch_optional(pool)
+    .await?
+    .is_some();
+    if is_adopted {
+        return Ok(());
+    }
+
+    let mut calendars = BTreeMap::<GuildId, String>::new();
+    for calendar in backend.list_calendars().await? {
+        let (Some(calendar_id), Some(summary)) = (calendar.id, calendar.summary) else {
+            continue;
+        };
+        let Ok(guild_id) = summary.parse::<u64>() else {
+            continue;
+        };
+        match calendars.entry(GuildId::new(guild_id)) {
+            Entry::Vacant(entry) => {
+                entry.insert(calendar_id);
+            }
+            Entry::Occupied(mut entry) => {
+                warn!(
+                    guild_id = ?entry.key(),
+                    "More than one calendar associated with the server, using the lowest id"
+                );
+                if calendar_id < *entry.get() {
+                    entry.insert(calendar_id);
+                }
+            }
+        }
+    }
+
+    for (guild_id, calendar_id) in calendars {
+        // A calendar created since the upgrade wins over the old one
+        if get_guild_calendar_id(pool, &guild_id).await?.is_some() {
+            continue;
+        }
+        info!(?guild_id, calendar_id, "Adopting the calendar");
+        set_guild_calendar(pool, &guild_id, &calendar_id).await?;
+    }
+    query!(
+        "
+        INSERT INTO calendar_adoption DEFAULT VALUES
+        "
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
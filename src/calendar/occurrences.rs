@@ -0,0 +1,284 @@
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use chrono_tz::Tz;
+use google_calendar3::api::{Event, EventDateTime};
+use rrule::{Frequency, RRule, RRuleSet, Unvalidated};
+
+use crate::{dates::DateFormat, Error};
+
+/// Limits the occurrences expanded for a single event, a daily event within a day yields one.
+const MAX_OCCURRENCES: u16 = 366;
+
+/// When an event, or one of its occurrences, starts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventStart {
+    AllDay(NaiveDate),
+    Timed(DateTime<Utc>),
+}
+
+impl EventStart {
+    pub fn from_event(event: &Event) -> Option<Self> {
+        match event.start {
+            Some(EventDateTime {
+                date_time: Some(date_time),
+                ..
+            }) => Some(Self::Timed(date_time)),
+            Some(EventDateTime {
+                date: Some(date), ..
+            }) => Some(Self::AllDay(date)),
+            _ => None,
+        }
+    }
+
+    /// All-day events start at midnight UTC.
+    pub fn date_time(&self) -> DateTime<Utc> {
+        match self {
+            Self::AllDay(date) => date.and_hms_opt(0, 0, 0).expect("Invalid time").and_utc(),
+            Self::Timed(date_time) => *date_time,
+        }
+    }
+
+    /// The date of the start in the time zone, all-day events keep their date.
+    pub fn local_date(&self, time_zone: &Tz) -> NaiveDate {
+        match self {
+            Self::AllDay(date) => *date,
+            Self::Timed(date_time) => date_time.with_timezone(time_zone).date_naive(),
+        }
+    }
+
+    /// Formats the start as seen in the time zone, all-day events have no time.
+    pub fn format_in(&self, time_zone: &Tz, format: DateFormat) -> String {
+        match self {
+            Self::AllDay(date) => format.format_date(*date),
+            Self::Timed(date_time) => format.format_date_time(&date_time.with_timezone(time_zone)),
+        }
+    }
+}
+
+/// The starts of the event occurrences between `from` and `to`, both inclusive.
+pub fn occurrences_between(
+    event: &Event,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<Vec<EventStart>, Error> {
+    occurrences(event, from, Some(to), MAX_OCCURRENCES)
+}
+
+/// The start of the first event occurrence since `from` and until `until` if it's set, `None`
+/// when there's none, e.g. once the event is over.
+pub fn next_occurrence(
+    event: &Event,
+    from: DateTime<Utc>,
+    until: Option<DateTime<Utc>>,
+) -> Result<Option<EventStart>, Error> {
+    Ok(occurrences(event, from, until, 1)?.into_iter().next())
+}
+
+fn occurrences(
+    event: &Event,
+    from: DateTime<Utc>,
+    to: Option<DateTime<Utc>>,
+    limit: u16,
+) -> Result<Vec<EventStart>, Error> {
+    let Some(start) = EventStart::from_event(event) else {
+        return Err(Error::MissingParameter("start".into()));
+    };
+    let recurrence = event.recurrence.as_deref().unwrap_or_default();
+    if recurrence.is_empty() {
+        let date_time = start.date_time();
+        let is_within = from <= date_time && to.is_none_or(|to| date_time <= to);
+        return Ok(if is_within { vec![start] } else { vec![] });
+    }
+
+    let rule_start = recurrence_start(event, start);
+    let time_zone = rule_start.timezone();
+    let mut rrule_set = RRuleSet::new(rule_start)
+        .set_from_string(&expandable_recurrence(recurrence))?
+        .after(from.with_timezone(&time_zone));
+    if let Some(to) = to {
+        rrule_set = rrule_set.before(to.with_timezone(&time_zone));
+    }
+    let occurrences = rrule_set
+        .all(limit)
+        .dates
+        .into_iter()
+        .map(|date_time| match start {
+            EventStart::AllDay(_) => EventStart::AllDay(date_time.date_naive()),
+            EventStart::Timed(_) => EventStart::Timed(date_time.with_timezone(&Utc)),
+        })
+        .collect();
+    Ok(occurrences)
+}
+
+/// The recurrence lines as the rrule crate expands them, see [`expandable_rule`].
+fn expandable_recurrence(recurrence: &[String]) -> String {
+    recurrence
+        .iter()
+        .map(|line| match line.strip_prefix("RRULE:") {
+            Some(rule) => format!("RRULE:{}", expandable_rule(rule)),
+            None => line.clone(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The rule with a date-only UNTIL, the one RFC 5545 asks for with an all-day start, read as
+/// the end of the date in UTC, the zone the all-day occurrences are expanded in. The rrule crate
+/// only takes an UNTIL in the zone of the start.
+pub(super) fn expandable_rule(rule: &str) -> String {
+    rule.split(';')
+        .map(|part| match part.strip_prefix("UNTIL=") {
+            Some(until) if until.len() == 8 && until.bytes().all(|c| c.is_ascii_digit()) => {
+                format!("{part}T235959Z")
+            }
+            _ => part.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+/// Where the recurrence of the event starting at `start` is expanded from. Timed events repeat
+/// at the same local time of their zone, even across DST changes.
+pub(super) fn recurrence_start(event: &Event, start: EventStart) -> DateTime<rrule::Tz> {
+    let time_zone: rrule::Tz = match start {
+        EventStart::AllDay(_) => Tz::UTC,
+        EventStart::Timed(_) => event_time_zone(event),
+    }
+    .into();
+    start.date_time().with_timezone(&time_zone)
+}
+
+/// How long every occurrence of the event lasts, a day for the all-day events without an end.
+pub fn event_duration(event: &Event) -> Duration {
+    let end = event.end.as_ref();
+    match EventStart::from_event(event) {
+        Some(EventStart::AllDay(start)) => end
+            .and_then(|end| end.date)
+            .map_or(Duration::days(1), |end| end - start),
+        Some(EventStart::Timed(start)) => end
+            .and_then(|end| end.date_time)
+            .map_or(Duration::zero(), |end| end - start),
+        None => Duration::zero(),
+    }
+}
+
+/// The last date the occurrence starting at `start` lasts on in the time zone, the start date
+/// for the events shorter than a day.
+pub fn occurrence_last_date(event: &Event, start: EventStart, time_zone: &Tz) -> NaiveDate {
+    let duration = event_duration(event);
+    match start {
+        // The end date of all-day events is exclusive
+        EventStart::AllDay(date) => date + Duration::days((duration.num_days() - 1).max(0)),
+        EventStart::Timed(date_time) => {
+            let end = (date_time + duration - Duration::seconds(1)).max(date_time);
+            end.with_timezone(time_zone).date_naive()
+        }
+    }
+}
+
+/// Formats the occurrence starting at `start`, with the last date for the multi-day events.
+pub fn format_occurrence(
+    event: &Event,
+    start: EventStart,
+    time_zone: &Tz,
+    format: DateFormat,
+) -> String {
+    let formatted = start.format_in(time_zone, format);
+    match occurrence_last_date(event, start, time_zone) {
+        last_date if last_date > start.local_date(time_zone) => {
+            format!("{formatted} to {}", format.format_date(last_date))
+        }
+        _ => formatted,
+    }
+}
+
+/// The time zone the event start is defined in, UTC if unknown.
+pub fn event_time_zone(event: &Event) -> Tz {
+    event
+        .start
+        .as_ref()
+        .and_then(|start| start.time_zone.as_ref())
+        .and_then(|time_zone| time_zone.parse().ok())
+        .unwrap_or(Tz::UTC)
+}
+
+/// Describes how the event repeats, e.g. `every 2 weeks`, `None` for a single event.
+pub fn format_recurrence(event: &Event, format: DateFormat) -> Option<String> {
+    let rule = event
+        .recurrence
+        .as_deref()?
+        .iter()
+        .find_map(|line| line.strip_prefix("RRULE:"))?;
+    let Ok(rrule) = rule.parse::<RRule<Unvalidated>>() else {
+        return Some(rule.into());
+    };
+    let unit = match rrule.get_freq() {
+        Frequency::Yearly => "year",
+        Frequency::Monthly => "month",
+        Frequency::Weekly => "week",
+        Frequency::Daily => "day",
+        Frequency::Hourly => "hour",
+        Frequency::Minutely => "minute",
+        Frequency::Secondly => "second",
+    };
+    let mut description = match rrule.get_interval() {
+        1 => format!("every {unit}"),
+        interval => format!("every {interval} {unit}s"),
+    };
+    if let Some(count) = rrule.get_count() {
+        description.push_str(&format!(", {count} times"));
+    }
+    if let Some(until) = rrule.get_until() {
+        // A date-only UNTIL is read as a local date, the others are shown in the event zone
+        let until = if until.timezone().is_local() {
+            until.date_naive()
+        } else {
+            until.with_timezone(&event_time_zone(event)).date_naive()
+        };
+        description.push_str(&format!(" until {}", format.format_date(until)));
+    }
+    Some(description)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, TimeZone, Utc};
+    use google_calendar3::api::{Event, EventDateTime};
+
+    use super::format_recurrence;
+    use crate::dates::DateFormat;
+
+    fn event(start: EventDateTime, rule: &str) -> Event {
+        Event {
+            start: Some(start),
+            recurrence: Some(vec![format!("RRULE:{rule}")]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn shows_the_until_date_in_the_event_zone() {
+        let start = EventDateTime {
+            date_time: Some(Utc.with_ymd_and_hms(2026, 12, 25, 17, 30, 0).unwrap()),
+            time_zone: Some("America/Los_Angeles".into()),
+            ..Default::default()
+        };
+        let event = event(start, "FREQ=WEEKLY;INTERVAL=2;UNTIL=20270101T075959Z");
+        assert_eq!(
+            format_recurrence(&event, DateFormat::Iso).as_deref(),
+            Some("every 2 weeks until 2026-12-31")
+        );
+    }
+
+    #[test]
+    fn keeps_a_date_only_until() {
+        let start = EventDateTime {
+            date: NaiveDate::from_ymd_opt(2026, 12, 25),
+            ..Default::default()
+        };
+        let event = event(start, "FREQ=DAILY;UNTIL=20261231");
+        assert_eq!(
+            format_recurrence(&event, DateFormat::Iso).as_deref(),
+            Some("every day until 2026-12-31")
+        );
+    }
+}
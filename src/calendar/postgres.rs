@@ -0,0 +1,256 @@
+use chrono::{Days, NaiveDate};
+use google_calendar3::api::{
+    Calendar, CalendarListEntry, Event, EventDateTime, EventExtendedProperties, EventSource,
+};
+use serenity::async_trait;
+use sqlx::{query, PgPool};
+use tracing::instrument;
+
+use crate::Error;
+
+use super::{event_url, CalendarBackend};
+
+/// Keeps calendars and events in the bot database, no external calendar service needed.
+#[derive(Clone, Debug)]
+pub struct PostgresBackend {
+    pool: PgPool,
+}
+
+impl PostgresBackend {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl CalendarBackend for PostgresBackend {
+    #[instrument(skip(self))]
+    async fn create_calendar(&self, name: &str) -> Result<Calendar, Error> {
+        let record = query!(
+            "
+            INSERT INTO calendars(summary)
+            VALUES($1)
+            RETURNING id
+            ",
+            name
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(Calendar {
+            id: Some(record.id),
+            summary: Some(name.to_string()),
+            ..Default::default()
+        })
+    }
+
+    #[instrument(skip(self))]
+    async fn delete_calendar(&self, calendar_id: &str) -> Result<(), Error> {
+        query!(
+            "
+            DELETE FROM calendars WHERE id = $1
+            ",
+            calendar_id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn create_event(&self, event: Event, calendar_id: &str) -> Result<Event, Error> {
+        let columns = EventColumns::new(&event)?;
+        let record = query!(
+            "
+            INSERT INTO events(
+                calendar_id, summary, description, location, url, start_date, end_date,
+                start_date_time, end_date_time, time_zone, recurrence, properties
+            )
+            VALUES($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+            RETURNING id
+            ",
+            calendar_id,
+            event.summary,
+            event.description,
+            event.location,
+            columns.url,
+            columns.start.date,
+            columns.end.date,
+            columns.start.date_time,
+            columns.end.date_time,
+            columns.start.time_zone,
+            &columns.recurrence,
+            columns.properties,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(Event {
+            id: Some(record.id),
+            ..event
+        })
+    }
+
+    #[instrument(skip(self))]
+    async fn update_event(&self, event: Event, calendar_id: &str) -> Result<Event, Error> {
+        let id = event.id.as_deref().ok_or(Error::MissingEventId)?;
+        let columns = EventColumns::new(&event)?;
+        let result = query!(
+            "
+            UPDATE events SET
+                summary = $3, description = $4, location = $5, url = $6, start_date = $7,
+                end_date = $8, start_date_time = $9, end_date_time = $10, time_zone = $11,
+                recurrence = $12, properties = $13
+            WHERE id = $1 AND calendar_id = $2
+            ",
+            id,
+            calendar_id,
+            event.summary,
+            event.description,
+            event.location,
+            columns.url,
+            columns.start.date,
+            columns.end.date,
+            columns.start.date_time,
+            columns.end.date_time,
+            columns.start.time_zone,
+            &columns.recurrence,
+            columns.properties,
+        )
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(Error::EventNotFound(id.into()));
+        }
+        Ok(event)
+    }
+
+    #[instrument(skip(self))]
+    async fn delete_event(&self, id: &str, calendar_id: &str) -> Result<(), Error> {
+        query!(
+            "
+            DELETE FROM events WHERE id = $1 AND calendar_id = $2
+            ",
+            id,
+            calendar_id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn list_calendars(&self) -> Result<Vec<CalendarListEntry>, Error> {
+        let calendars = query!(
+            "
+            SELECT id, summary FROM calendars
+            ORDER BY created_at
+            "
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|record| CalendarListEntry {
+            id: Some(record.id),
+            summary: Some(record.summary),
+            ..Default::default()
+        })
+        .collect();
+        Ok(calendars)
+    }
+
+    #[instrument(skip(self))]
+    async fn list_events(&self, calendar_id: &str) -> Result<Vec<Event>, Error> {
+        let events = query!(
+            "
+            SELECT
+                id, summary, description, location, url, start_date, end_date,
+                start_date_time, end_date_time, time_zone, recurrence, properties
+            FROM events
+            WHERE calendar_id = $1
+            ORDER BY COALESCE(start_date_time, start_date)
+            ",
+            calendar_id
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|record| Event {
+            id: Some(record.id),
+            summary: record.summary,
+            description: record.description,
+            location: record.location,
+            source: record.url.map(|url| EventSource {
+                url: Some(url),
+                ..Default::default()
+            }),
+            start: Some(EventDateTime {
+                date: record.start_date,
+                date_time: record.start_date_time,
+                time_zone: record.time_zone.clone(),
+            }),
+            end: Some(EventDateTime {
+                date: record.end_date,
+                date_time: record.end_date_time,
+                time_zone: record.time_zone,
+            }),
+            recurrence: Some(record.recurrence),
+            extended_properties: Some(EventExtendedProperties {
+                private: serde_json::from_value(record.properties).ok(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+        .collect();
+        Ok(events)
+    }
+
+    fn calendar_url(&self, _calendar_id: &str) -> Option<String> {
+        None
+    }
+}
+
+/// The event fields as they are stored in the events table.
+struct EventColumns {
+    start: EventDateTime,
+    end: EventDateTime,
+    recurrence: Vec<String>,
+    properties: serde_json::Value,
+    url: Option<String>,
+}
+
+impl EventColumns {
+    fn new(event: &Event) -> Result<Self, Error> {
+        let start = event
+            .start
+            .clone()
+            .ok_or(Error::MissingParameter("start".into()))?;
+        let end = match (&event.end, start.date) {
+            (Some(end), _) => end.clone(),
+            (None, Some(date)) => date_time(
+                date.checked_add_days(Days::new(1))
+                    .expect("Out of range days"),
+            ),
+            (None, None) => start.clone(),
+        };
+        let properties = serde_json::to_value(
+            event
+                .extended_properties
+                .as_ref()
+                .and_then(|properties| properties.private.clone())
+                .unwrap_or_default(),
+        )?;
+        Ok(Self {
+            start,
+            end,
+            recurrence: event.recurrence.clone().unwrap_or_default(),
+            properties,
+            url: event_url(event).map(Into::into),
+        })
+    }
+}
+
+fn date_time(date: NaiveDate) -> EventDateTime {
+    EventDateTime {
+        date: Some(date),
+        ..Default::default()
+    }
+}
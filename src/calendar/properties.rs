@@ -0,0 +1,54 @@
+use google_calendar3::api::{Event, EventExtendedProperties, EventSource};
+
+/// Days before an occurrence to remind about it, e.g. `7,1,0`.
+pub const REMINDER_DAYS_PROPERTY: &str = "reminder_days";
+/// The image shown in the embed notifications of the event.
+pub const IMAGE_URL_PROPERTY: &str = "image_url";
+/// Picks the notification templates of the category, e.g. `birthday` or `meeting`.
+pub const CATEGORY_PROPERTY: &str = "category";
+/// Comma separated ids of the roles and the users the notifications ping.
+pub const MENTION_ROLES_PROPERTY: &str = "mention_roles";
+pub const MENTION_USERS_PROPERTY: &str = "mention_users";
+
+/// Reads the bot's own event metadata, kept in the private extended properties.
+pub fn get_event_property<'a>(event: &'a Event, key: &str) -> Option<&'a str> {
+    event
+        .extended_properties
+        .as_ref()?
+        .private
+        .as_ref()?
+        .get(key)
+        .map(String::as_str)
+}
+
+pub fn remove_event_property(event: &mut Event, key: &str) {
+    if let Some(properties) = event
+        .extended_properties
+        .as_mut()
+        .and_then(|properties| properties.private.as_mut())
+    {
+        properties.remove(key);
+    }
+}
+
+pub fn set_event_property(event: &mut Event, key: &str, value: String) {
+    event
+        .extended_properties
+        .get_or_insert_with(EventExtendedProperties::default)
+        .private
+        .get_or_insert_with(Default::default)
+        .insert(key.into(), value);
+}
+
+/// The link of the event, kept as its source so Google shows it too. The source has no title,
+/// which would go stale once the event is renamed.
+pub fn event_url(event: &Event) -> Option<&str> {
+    event.source.as_ref()?.url.as_deref()
+}
+
+pub fn set_event_url(event: &mut Event, url: Option<String>) {
+    event.source = url.map(|url| EventSource {
+        title: None,
+        url: Some(url),
+    });
+}
@@ -0,0 +1,427 @@
+use chrono::{Days, Duration, NaiveDate, NaiveTime, TimeZone, Utc};
+use google_calendar3::api::Event;
+use rrule::{Frequency, RRule, Unvalidated};
+
+use crate::Error;
+
+use super::{event_time_zone, expandable_rule, next_occurrence, recurrence_start, EventStart};
+
+/// How often an event repeats, the choices of the `repeat` options.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Repeat {
+    Once,
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+impl Repeat {
+    /// The choice names and values.
+    pub const CHOICES: [(&'static str, &'static str); 5] = [
+        ("Once", "once"),
+        ("Daily", "daily"),
+        ("Weekly", "weekly"),
+        ("Monthly", "monthly"),
+        ("Yearly", "yearly"),
+    ];
+
+    pub fn parse(repeat: &str) -> Result<Self, Error> {
+        match repeat {
+            "once" => Ok(Self::Once),
+            "daily" => Ok(Self::Daily),
+            "weekly" => Ok(Self::Weekly),
+            "monthly" => Ok(Self::Monthly),
+            "yearly" => Ok(Self::Yearly),
+            repeat => Err(Error::InvalidRecurrence(format!("unknown repeat {repeat}"))),
+        }
+    }
+
+    fn frequency(self) -> Option<&'static str> {
+        match self {
+            Self::Once => None,
+            Self::Daily => Some("DAILY"),
+            Self::Weekly => Some("WEEKLY"),
+            Self::Monthly => Some("MONTHLY"),
+            Self::Yearly => Some("YEARLY"),
+        }
+    }
+}
+
+/// How an event repeats as picked in a command.
+#[derive(Debug, Default)]
+pub struct RecurrenceOptions<'a> {
+    pub repeat: Option<Repeat>,
+    /// Repeats every `interval` days, weeks, months or years.
+    pub interval: Option<i64>,
+    /// The number of occurrences.
+    pub count: Option<i64>,
+    /// The last date an occurrence may be on.
+    pub until: Option<NaiveDate>,
+    /// A custom RRULE replacing the other options, e.g. `FREQ=WEEKLY;BYDAY=FR`.
+    pub rule: Option<&'a str>,
+}
+
+impl RecurrenceOptions<'_> {
+    pub fn is_empty(&self) -> bool {
+        self.repeat.is_none()
+            && self.interval.is_none()
+            && self.count.is_none()
+            && self.until.is_none()
+            && self.rule.is_none()
+    }
+}
+
+/// Builds the recurrence of the event from the options, `None` for a single event. The rule is
+/// validated against the event start, so the backends and the notifier can expand it.
+///
+/// Without the repeat, the interval, the count or the until change the rule the event already
+/// repeats by, keeping its frequency and its other parts.
+pub fn build_recurrence(
+    event: &Event,
+    options: &RecurrenceOptions,
+) -> Result<Option<Vec<String>>, Error> {
+    let has_limits =
+        options.interval.is_some() || options.count.is_some() || options.until.is_some();
+    if let Some(rule) = options.rule {
+        if options.repeat.is_some() || has_limits {
+            return Err(Error::InvalidRecurrence(
+                "a custom rule replaces the repeat, interval, count and until".into(),
+            ));
+        }
+        return Ok(Some(vec![validate_rule(event, rule)?]));
+    }
+
+    let Some(repeat) = options.repeat else {
+        return update_rule(event, options);
+    };
+    let Some(frequency) = repeat.frequency() else {
+        if has_limits {
+            return Err(Error::InvalidRecurrence(
+                "a single event has no interval, count or until".into(),
+            ));
+        }
+        return Ok(None);
+    };
+    if options.count.is_some() && options.until.is_some() {
+        return Err(Error::InvalidRecurrence(
+            "set either the count or until".into(),
+        ));
+    }
+
+    let rule = push_limits(event, format!("FREQ={frequency}"), options)?;
+    Ok(Some(vec![validate_rule(event, &rule)?]))
+}
+
+/// Sets the interval, the count or the until of the event rule, the other recurrence lines kept.
+fn update_rule(event: &Event, options: &RecurrenceOptions) -> Result<Option<Vec<String>>, Error> {
+    let recurrence = event.recurrence.clone().unwrap_or_default();
+    let Some(index) = recurrence
+        .iter()
+        .position(|line| line.starts_with("RRULE:"))
+    else {
+        return Err(Error::InvalidRecurrence(
+            "pick how the event repeats to set the interval, count or until".into(),
+        ));
+    };
+    if options.count.is_some() && options.until.is_some() {
+        return Err(Error::InvalidRecurrence(
+            "set either the count or until".into(),
+        ));
+    }
+
+    let replaced = |part: &str| match part.split_once('=').map(|(name, _)| name) {
+        Some("INTERVAL") => options.interval.is_some(),
+        // The count and the until exclude each other
+        Some("COUNT" | "UNTIL") => options.count.is_some() || options.until.is_some(),
+        _ => false,
+    };
+    let rule = recurrence[index]["RRULE:".len()..]
+        .split(';')
+        .filter(|part| !replaced(part))
+        .collect::<Vec<_>>()
+        .join(";");
+    let mut recurrence = recurrence;
+    recurrence[index] = validate_rule(event, &push_limits(event, rule, options)?)?;
+    Ok(Some(recurrence))
+}
+
+fn push_limits(
+    event: &Event,
+    mut rule: String,
+    options: &RecurrenceOptions,
+) -> Result<String, Error> {
+    if let Some(interval) = options.interval {
+        rule.push_str(&format!(";INTERVAL={interval}"));
+    }
+    if let Some(count) = options.count {
+        rule.push_str(&format!(";COUNT={count}"));
+    }
+    if let Some(until) = options.until {
+        rule.push_str(&format!(";UNTIL={}", format_until(event, until)?));
+    }
+    Ok(rule)
+}
+
+/// Checks the rules of the event still fit its start, e.g. once it's moved, and it happens at
+/// least once, so it doesn't silently disappear.
+pub fn validate_recurrence(event: &Event) -> Result<(), Error> {
+    let recurrence = event.recurrence.as_deref().unwrap_or_default();
+    for rule in recurrence
+        .iter()
+        .filter_map(|line| line.strip_prefix("RRULE:"))
+    {
+        validate_rule(event, rule)?;
+    }
+    let start = EventStart::from_event(event).ok_or(Error::MissingParameter("start".into()))?;
+    if next_occurrence(event, start.date_time(), None)?.is_none() {
+        return Err(Error::InvalidRecurrence(
+            "the event would never happen, change how it repeats".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// The date for the all-day events, which RFC 5545 wants of the same type as their start, and
+/// the end of the date in the event time zone as a UTC time for the timed ones.
+fn format_until(event: &Event, until: NaiveDate) -> Result<String, Error> {
+    let start = EventStart::from_event(event).ok_or(Error::MissingParameter("start".into()))?;
+    if until < start.local_date(&event_time_zone(event)) {
+        return Err(Error::InvalidRecurrence(format!(
+            "the event starts after {until}"
+        )));
+    }
+    if let EventStart::AllDay(_) = start {
+        return Ok(until.format("%Y%m%d").to_string());
+    }
+    let midnight = (until + Days::new(1)).and_time(NaiveTime::MIN);
+    let end = event_time_zone(event)
+        .from_local_datetime(&midnight)
+        .earliest()
+        .ok_or(Error::NonexistentLocalTime(midnight))?
+        .with_timezone(&Utc)
+        - Duration::seconds(1);
+    Ok(end.format("%Y%m%dT%H%M%SZ").to_string())
+}
+
+/// Checks the RRULE, with or without the `RRULE:` prefix, repeats at most daily and fits the
+/// event start, returning the recurrence line.
+fn validate_rule(event: &Event, rule: &str) -> Result<String, Error> {
+    let rule = rule.trim();
+    let rule = rule.strip_prefix("RRULE:").unwrap_or(rule);
+    let rrule = expandable_rule(rule)
+        .parse::<RRule<Unvalidated>>()
+        .map_err(|why| Error::InvalidRecurrence(why.to_string()))?;
+    let is_daily_at_most = matches!(
+        rrule.get_freq(),
+        Frequency::Daily | Frequency::Weekly | Frequency::Monthly | Frequency::Yearly
+    );
+    // The times of the day would repeat a daily event within the day
+    let has_times = !rrule.get_by_hour().is_empty()
+        || !rrule.get_by_minute().is_empty()
+        || !rrule.get_by_second().is_empty();
+    if !is_daily_at_most || has_times {
+        return Err(Error::InvalidRecurrence(
+            "events repeat daily at most".into(),
+        ));
+    }
+    let start = EventStart::from_event(event).ok_or(Error::MissingParameter("start".into()))?;
+    rrule
+        .validate(recurrence_start(event, start))
+        .map_err(|why| Error::InvalidRecurrence(why.to_string()))?;
+    Ok(format!("RRULE:{rule}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, TimeZone, Utc};
+    use google_calendar3::api::{Event, EventDateTime};
+
+    use super::{build_recurrence, validate_recurrence, RecurrenceOptions, Repeat};
+
+    fn date(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, month, day).unwrap()
+    }
+
+    fn all_day_event(recurrence: Option<&str>) -> Event {
+        Event {
+            start: Some(EventDateTime {
+                date: Some(date(12, 25)),
+                ..Default::default()
+            }),
+            recurrence: recurrence.map(|rule| vec![rule.into()]),
+            ..Default::default()
+        }
+    }
+
+    fn timed_event() -> Event {
+        Event {
+            start: Some(EventDateTime {
+                date_time: Some(Utc.with_ymd_and_hms(2026, 12, 25, 17, 30, 0).unwrap()),
+                time_zone: Some("Europe/Berlin".into()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn build(event: &Event, options: RecurrenceOptions) -> Result<Option<Vec<String>>, String> {
+        build_recurrence(event, &options).map_err(|why| why.to_string())
+    }
+
+    #[test]
+    fn builds_the_rule_from_the_options() {
+        let options = RecurrenceOptions {
+            repeat: Some(Repeat::Weekly),
+            interval: Some(2),
+            count: Some(5),
+            ..Default::default()
+        };
+        assert_eq!(
+            build(&all_day_event(None), options),
+            Ok(Some(vec!["RRULE:FREQ=WEEKLY;INTERVAL=2;COUNT=5".into()]))
+        );
+    }
+
+    #[test]
+    fn keeps_a_single_event_single() {
+        let options = RecurrenceOptions {
+            repeat: Some(Repeat::Once),
+            ..Default::default()
+        };
+        assert_eq!(build(&all_day_event(None), options), Ok(None));
+
+        let options = RecurrenceOptions {
+            repeat: Some(Repeat::Once),
+            count: Some(2),
+            ..Default::default()
+        };
+        assert!(build(&all_day_event(None), options).is_err());
+    }
+
+    #[test]
+    fn writes_a_date_until_for_all_day_events() {
+        let options = RecurrenceOptions {
+            repeat: Some(Repeat::Daily),
+            until: Some(date(12, 31)),
+            ..Default::default()
+        };
+        assert_eq!(
+            build(&all_day_event(None), options),
+            Ok(Some(vec!["RRULE:FREQ=DAILY;UNTIL=20261231".into()]))
+        );
+    }
+
+    #[test]
+    fn writes_the_end_of_the_local_date_for_timed_events() {
+        let options = RecurrenceOptions {
+            repeat: Some(Repeat::Daily),
+            until: Some(date(12, 31)),
+            ..Default::default()
+        };
+        assert_eq!(
+            build(&timed_event(), options),
+            Ok(Some(vec!["RRULE:FREQ=DAILY;UNTIL=20261231T225959Z".into()]))
+        );
+    }
+
+    #[test]
+    fn rejects_an_until_before_the_start() {
+        let options = RecurrenceOptions {
+            repeat: Some(Repeat::Daily),
+            until: Some(date(12, 24)),
+            ..Default::default()
+        };
+        assert!(build(&all_day_event(None), options).is_err());
+    }
+
+    #[test]
+    fn rejects_both_the_count_and_until() {
+        let options = RecurrenceOptions {
+            repeat: Some(Repeat::Daily),
+            count: Some(3),
+            until: Some(date(12, 31)),
+            ..Default::default()
+        };
+        assert!(build(&all_day_event(None), options).is_err());
+    }
+
+    #[test]
+    fn changes_the_limits_of_the_event_rule() {
+        let event = all_day_event(Some("RRULE:FREQ=WEEKLY;BYDAY=FR;UNTIL=20270101"));
+        let options = RecurrenceOptions {
+            count: Some(4),
+            ..Default::default()
+        };
+        assert_eq!(
+            build(&event, options),
+            Ok(Some(vec!["RRULE:FREQ=WEEKLY;BYDAY=FR;COUNT=4".into()]))
+        );
+    }
+
+    #[test]
+    fn asks_for_the_repeat_of_a_single_event() {
+        let options = RecurrenceOptions {
+            interval: Some(2),
+            ..Default::default()
+        };
+        assert!(build(&all_day_event(None), options).is_err());
+    }
+
+    #[test]
+    fn accepts_custom_rules_with_or_without_the_prefix() {
+        for rule in [
+            "FREQ=MONTHLY;BYMONTHDAY=25",
+            " RRULE:FREQ=MONTHLY;BYMONTHDAY=25 ",
+        ] {
+            let options = RecurrenceOptions {
+                rule: Some(rule),
+                ..Default::default()
+            };
+            assert_eq!(
+                build(&all_day_event(None), options),
+                Ok(Some(vec!["RRULE:FREQ=MONTHLY;BYMONTHDAY=25".into()]))
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_rules_repeating_within_a_day() {
+        for rule in [
+            "FREQ=HOURLY",
+            "FREQ=DAILY;BYHOUR=0,12",
+            "FREQ=DAILY;BYMINUTE=0,30",
+            "FREQ=WEEKLY;BYSECOND=1",
+            "not a rule",
+        ] {
+            let options = RecurrenceOptions {
+                rule: Some(rule),
+                ..Default::default()
+            };
+            assert!(build(&all_day_event(None), options).is_err(), "{rule}");
+        }
+    }
+
+    #[test]
+    fn rejects_a_custom_rule_with_the_options() {
+        let options = RecurrenceOptions {
+            repeat: Some(Repeat::Daily),
+            rule: Some("FREQ=DAILY"),
+            ..Default::default()
+        };
+        assert!(build(&all_day_event(None), options).is_err());
+    }
+
+    #[test]
+    fn accepts_an_event_happening() {
+        assert!(validate_recurrence(&all_day_event(None)).is_ok());
+        assert!(validate_recurrence(&all_day_event(Some("RRULE:FREQ=DAILY;COUNT=1"))).is_ok());
+        assert!(validate_recurrence(&all_day_event(Some("RRULE:FREQ=YEARLY"))).is_ok());
+    }
+
+    #[test]
+    fn rejects_an_event_never_happening() {
+        let event = all_day_event(Some("RRULE:FREQ=DAILY;UNTIL=20261201"));
+        assert!(validate_recurrence(&event).is_err());
+    }
+}
@@ -11,8 +11,58 @@ pub struct AppConfig {
     #[serde(with = "humantime_serde")]
     pub notification_period: Duration,
     pub discord_access_token: Secret<String>,
-    pub google_secret: Secret<String>,
+    pub google_secret: Option<Secret<String>>,
     pub db: DbConfig,
+    #[serde(default)]
+    pub calendar: CalendarConfig,
+}
+
+#[derive(Deserialize, Default)]
+pub struct CalendarConfig {
+    #[serde(default)]
+    pub backend: CalendarBackendKind,
+    #[serde(default)]
+    pub google: GoogleConfig,
+    pub caldav: Option<CalDavConfig>,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct GoogleConfig {
+    /// Keeps the bot under the project quota, `0` doesn't limit the rate.
+    pub requests_per_second: u32,
+    /// How many times a request failing with a transient error is retried.
+    pub max_retries: u32,
+    #[serde(with = "humantime_serde")]
+    pub request_timeout: Duration,
+}
+
+impl Default for GoogleConfig {
+    fn default() -> Self {
+        Self {
+            requests_per_second: 5,
+            max_retries: 5,
+            request_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CalDavConfig {
+    /// The home collection the guild calendars are created in.
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<Secret<String>>,
+}
+
+/// Where the guild calendars and events are stored.
+#[derive(Deserialize, Default, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum CalendarBackendKind {
+    #[default]
+    Google,
+    Postgres,
+    CalDav,
 }
 
 #[derive(Deserialize)]
@@ -54,8 +104,10 @@ impl AppConfig {
         let config = config.add_source(config::File::with_name("config"));
         let discord_token = fs::read_to_string(secrets_path.join("discord-token.txt"))?;
         let config = config.set_override("discord_access_token", discord_token)?;
-        let google_secret = fs::read_to_string(secrets_path.join("google-sa-secret.json"))?;
-        let config = config.set_override("google_secret", google_secret)?;
+        let google_secret = fs::read_to_string(secrets_path.join("google-sa-secret.json")).ok();
+        let config = config.set_override_option("google_secret", google_secret)?;
+        let caldav_password = fs::read_to_string(secrets_path.join("caldav_password.txt")).ok();
+        let config = config.set_override_option("calendar.caldav.password", caldav_password)?;
         let db_password = fs::read_to_string(secrets_path.join("db_password.txt"))?;
         let config = config.set_override("db.password", db_password)?;
         let config = config.build()?;
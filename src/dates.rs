@@ -0,0 +1,349 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, Days, Month, Months, NaiveDate, TimeZone, Weekday};
+
+use crate::Error;
+
+/// How a server writes the dates, both in the commands and in the bot messages.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DateFormat {
+    /// `2026-12-25`, `12-25`
+    #[default]
+    Iso,
+    /// `12/25/2026`, `12/25`
+    Us,
+    /// `25.12.2026`, `25.12`, `25/12`
+    Eu,
+}
+
+impl DateFormat {
+    pub fn parse(format: &str) -> Result<Self, Error> {
+        match format {
+            "iso" => Ok(Self::Iso),
+            "us" => Ok(Self::Us),
+            "eu" => Ok(Self::Eu),
+            format => Err(Error::InvalidDateFormat(format.into())),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Iso => "iso",
+            Self::Us => "us",
+            Self::Eu => "eu",
+        }
+    }
+
+    pub fn format_date(&self, date: NaiveDate) -> String {
+        let format = match self {
+            Self::Iso => "%Y-%m-%d",
+            Self::Us => "%m/%d/%Y",
+            Self::Eu => "%d.%m.%Y",
+        };
+        date.format(format).to_string()
+    }
+
+    pub fn format_date_time<Tz: TimeZone>(&self, date_time: &DateTime<Tz>) -> String
+    where
+        Tz::Offset: std::fmt::Display,
+    {
+        let format = match self {
+            Self::Iso => "%Y-%m-%d %H:%M %Z",
+            Self::Us => "%m/%d/%Y %-I:%M %p %Z",
+            Self::Eu => "%d.%m.%Y %H:%M %Z",
+        };
+        date_time.format(format).to_string()
+    }
+
+    fn separators(&self) -> &'static [char] {
+        match self {
+            Self::Iso => &['-'],
+            Self::Us => &['/', '-'],
+            Self::Eu => &['.', '/'],
+        }
+    }
+}
+
+/// Parses the dates typed in the commands, `today` being the date in the server time zone:
+/// - `YYYY-MM-DD` and the full dates of the server format, e.g. `25.12.2026` or `25.12.26`
+/// - the short dates of the server format, e.g. `12-25`, and `25 dec`, `December 25th`, the next
+///   such date, so a date passed this year is the next year one, unless the year follows, e.g.
+///   `25 dec 2027`
+/// - `today`, `tomorrow`, `in 3 days`, `in a week`, `next month`
+/// - `friday` or `this friday` from today on, `next friday` after today
+pub fn parse_date(input: &str, today: NaiveDate, format: DateFormat) -> Result<NaiveDate, Error> {
+    let input = input.trim();
+    let normalized = input.to_lowercase();
+    let words = normalized
+        .split(|c: char| c.is_whitespace() || c == ',' || c == '.')
+        .filter(|word| !word.is_empty() && *word != "of" && *word != "the")
+        .collect::<Vec<_>>();
+
+    parse_numeric(&normalized, today, format)
+        .or_else(|| parse_relative(&words, today))
+        .or_else(|| parse_weekday(&words, today))
+        .or_else(|| parse_day_month(&words, today))
+        .ok_or_else(|| Error::InvalidDate(input.into()))
+}
+
+/// Tells how a typed date was read, so it can be checked, `None` for a full date.
+pub fn describe_parsed_date(input: &str, date: NaiveDate, format: DateFormat) -> Option<String> {
+    let input = input.trim();
+    let formatted = format.format_date(date);
+    (input != formatted && input != date.to_string())
+        .then(|| format!("\"{input}\" was read as {}, {formatted}", date.format("%A")))
+}
+
+fn parse_numeric(input: &str, today: NaiveDate, format: DateFormat) -> Option<NaiveDate> {
+    if let Ok(date) = NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+        return Some(date);
+    }
+    let parts = input
+        .trim_end_matches('.')
+        .split(format.separators())
+        .collect::<Vec<_>>();
+    let (month, day, year) = match (format, parts.as_slice()) {
+        (DateFormat::Eu, [day, month]) => (month, day, None),
+        (DateFormat::Eu, [day, month, year]) => (month, day, Some(year)),
+        (DateFormat::Iso | DateFormat::Us, [month, day]) => (month, day, None),
+        (DateFormat::Us, [month, day, year]) => (month, day, Some(year)),
+        _ => return None,
+    };
+    let (month, day) = (month.parse().ok()?, day.parse().ok()?);
+    match year {
+        Some(year) => NaiveDate::from_ymd_opt(parse_year(year)?, month, day),
+        None => next_date(month, day, today),
+    }
+}
+
+/// A four-digit year, or a two-digit one of this century, e.g. `26` for 2026.
+fn parse_year(year: &str) -> Option<i32> {
+    if !year.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    match year.len() {
+        2 => Some(2000 + year.parse::<i32>().ok()?),
+        4 => year.parse().ok(),
+        _ => None,
+    }
+}
+
+fn parse_relative(words: &[&str], today: NaiveDate) -> Option<NaiveDate> {
+    match words {
+        ["today"] => Some(today),
+        ["tomorrow"] => today.checked_add_days(Days::new(1)),
+        ["in", count, unit] => {
+            let count = match *count {
+                "a" | "an" | "one" => 1,
+                count => count.parse().ok()?,
+            };
+            add_units(today, count, unit)
+        }
+        ["next", unit @ ("week" | "month" | "year")] => add_units(today, 1, unit),
+        _ => None,
+    }
+}
+
+fn add_units(date: NaiveDate, count: u32, unit: &str) -> Option<NaiveDate> {
+    match unit.strip_suffix('s').unwrap_or(unit) {
+        "day" => date.checked_add_days(Days::new(count.into())),
+        "week" => date.checked_add_days(Days::new(u64::from(count) * 7)),
+        "month" => date.checked_add_months(Months::new(count)),
+        "year" => date.checked_add_months(Months::new(count.checked_mul(12)?)),
+        _ => None,
+    }
+}
+
+fn parse_weekday(words: &[&str], today: NaiveDate) -> Option<NaiveDate> {
+    let (weekday, skip_today) = match words {
+        [weekday] | ["this", weekday] => (weekday, false),
+        ["next", weekday] => (weekday, true),
+        _ => return None,
+    };
+    let weekday = Weekday::from_str(weekday).ok()?;
+    let mut days =
+        (7 + weekday.num_days_from_monday() - today.weekday().num_days_from_monday()) % 7;
+    if days == 0 && skip_today {
+        days = 7;
+    }
+    today.checked_add_days(Days::new(days.into()))
+}
+
+/// `25 dec`, `dec 25`, `25th december 2027` and the like.
+fn parse_day_month(words: &[&str], today: NaiveDate) -> Option<NaiveDate> {
+    let (day, month, year) = match words {
+        [first, second] => (first, second, None),
+        [first, second, year] => (first, second, Some(parse_year(year)?)),
+        _ => return None,
+    };
+    let (day, month) = match (parse_day(day), Month::from_str(month)) {
+        (Some(day), Ok(month)) => (day, month),
+        _ => (parse_day(month)?, Month::from_str(day).ok()?),
+    };
+    match year {
+        Some(year) => NaiveDate::from_ymd_opt(year, month.number_from_month(), day),
+        None => next_date(month.number_from_month(), day, today),
+    }
+}
+
+/// A day of the month, optionally with the ordinal suffix, e.g. `25th`.
+fn parse_day(day: &str) -> Option<u32> {
+    let day = ["st", "nd", "rd", "th"]
+        .iter()
+        .find_map(|suffix| day.strip_suffix(suffix))
+        .unwrap_or(day);
+    day.parse().ok()
+}
+
+/// The first date on the month and the day since `today`.
+fn next_date(month: u32, day: u32, today: NaiveDate) -> Option<NaiveDate> {
+    // February 29 waits for the next leap year
+    (today.year()..=today.year() + 8)
+        .filter_map(|year| NaiveDate::from_ymd_opt(year, month, day))
+        .find(|date| *date >= today)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::{describe_parsed_date, parse_date, DateFormat};
+
+    /// A Sunday.
+    fn today() -> NaiveDate {
+        date(2026, 10, 18)
+    }
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn parse(input: &str) -> Option<NaiveDate> {
+        parse_date(input, today(), DateFormat::Iso).ok()
+    }
+
+    #[test]
+    fn parses_full_dates() {
+        assert_eq!(parse("2027-01-02"), Some(date(2027, 1, 2)));
+        assert_eq!(parse(" 2025-12-25 "), Some(date(2025, 12, 25)));
+    }
+
+    #[test]
+    fn rolls_short_dates_to_the_next_one() {
+        assert_eq!(parse("12-25"), Some(date(2026, 12, 25)));
+        assert_eq!(parse("10-18"), Some(today()));
+        assert_eq!(parse("10-01"), Some(date(2027, 10, 1)));
+    }
+
+    #[test]
+    fn waits_for_the_next_february_29() {
+        assert_eq!(parse("02-29"), Some(date(2028, 2, 29)));
+        assert_eq!(parse("29 feb"), Some(date(2028, 2, 29)));
+    }
+
+    #[test]
+    fn parses_relative_dates() {
+        assert_eq!(parse("today"), Some(today()));
+        assert_eq!(parse("Tomorrow"), Some(date(2026, 10, 19)));
+        assert_eq!(parse("in 3 days"), Some(date(2026, 10, 21)));
+        assert_eq!(parse("in a week"), Some(date(2026, 10, 25)));
+        assert_eq!(parse("in 2 months"), Some(date(2026, 12, 18)));
+        assert_eq!(parse("in one year"), Some(date(2027, 10, 18)));
+        assert_eq!(parse("next week"), Some(date(2026, 10, 25)));
+        assert_eq!(parse("next month"), Some(date(2026, 11, 18)));
+    }
+
+    #[test]
+    fn parses_weekdays() {
+        assert_eq!(parse("friday"), Some(date(2026, 10, 23)));
+        assert_eq!(parse("this fri"), Some(date(2026, 10, 23)));
+        assert_eq!(parse("next friday"), Some(date(2026, 10, 23)));
+        // Today is a Sunday
+        assert_eq!(parse("sunday"), Some(today()));
+        assert_eq!(parse("this sunday"), Some(today()));
+        assert_eq!(parse("next sunday"), Some(date(2026, 10, 25)));
+    }
+
+    #[test]
+    fn parses_days_and_month_names() {
+        assert_eq!(parse("25 dec"), Some(date(2026, 12, 25)));
+        assert_eq!(parse("Dec 25th"), Some(date(2026, 12, 25)));
+        assert_eq!(parse("December 25th"), Some(date(2026, 12, 25)));
+        assert_eq!(parse("1st of January"), Some(date(2027, 1, 1)));
+        assert_eq!(parse("the 2nd of march"), Some(date(2027, 3, 2)));
+        assert_eq!(parse("25th of December 2027"), Some(date(2027, 12, 25)));
+        assert_eq!(parse("dec 25, 2025"), Some(date(2025, 12, 25)));
+    }
+
+    #[test]
+    fn rejects_invalid_dates() {
+        for input in [
+            "",
+            "garbage",
+            "13-01",
+            "02-30",
+            "32 dec",
+            "in days",
+            "next decade",
+        ] {
+            assert_eq!(parse(input), None, "{input}");
+        }
+    }
+
+    #[test]
+    fn reads_the_server_format() {
+        let parse = |input, format| parse_date(input, today(), format).ok();
+        assert_eq!(parse("12/25", DateFormat::Us), Some(date(2026, 12, 25)));
+        assert_eq!(
+            parse("12/25/2027", DateFormat::Us),
+            Some(date(2027, 12, 25))
+        );
+        assert_eq!(parse("25.12", DateFormat::Eu), Some(date(2026, 12, 25)));
+        assert_eq!(
+            parse("25/12/2027", DateFormat::Eu),
+            Some(date(2027, 12, 25))
+        );
+        assert_eq!(parse("25.12.", DateFormat::Eu), Some(date(2026, 12, 25)));
+        // ISO dates are read whatever the format
+        assert_eq!(
+            parse("2027-12-25", DateFormat::Eu),
+            Some(date(2027, 12, 25))
+        );
+        assert_eq!(parse("12/25", DateFormat::Eu), None);
+        assert_eq!(parse("25.12", DateFormat::Iso), None);
+    }
+
+    #[test]
+    fn reads_two_digit_years_in_this_century() {
+        let parse = |input, format| parse_date(input, today(), format).ok();
+        assert_eq!(parse("25.12.26", DateFormat::Eu), Some(date(2026, 12, 25)));
+        assert_eq!(parse("12/25/27", DateFormat::Us), Some(date(2027, 12, 25)));
+        assert_eq!(
+            parse("25 dec 27", DateFormat::Iso),
+            Some(date(2027, 12, 25))
+        );
+        for input in ["25.12.6", "25.12.026", "25.12.+26", "25.12.20266"] {
+            assert_eq!(parse(input, DateFormat::Eu), None, "{input}");
+        }
+    }
+
+    #[test]
+    fn describes_the_dates_not_typed_in_full() {
+        assert_eq!(
+            describe_parsed_date("next friday", date(2026, 10, 23), DateFormat::Iso),
+            Some("\"next friday\" was read as Friday, 2026-10-23".into())
+        );
+        assert_eq!(
+            describe_parsed_date("12/25", date(2026, 12, 25), DateFormat::Us),
+            Some("\"12/25\" was read as Friday, 12/25/2026".into())
+        );
+        assert_eq!(
+            describe_parsed_date("2026-12-25", date(2026, 12, 25), DateFormat::Eu),
+            None
+        );
+        assert_eq!(
+            describe_parsed_date("25.12.2026", date(2026, 12, 25), DateFormat::Eu),
+            None
+        );
+    }
+}
@@ -3,3 +3,5 @@ pub use client::*;
 mod commands;
 mod handler;
 pub use handler::*;
+mod labels;
+pub use labels::*;
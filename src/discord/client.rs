@@ -1,6 +1,9 @@
+use chrono_tz::Tz;
 use serenity::all::{ChannelId, GuildId};
 use sqlx::{query, PgPool};
 
+use crate::{dates::DateFormat, notifier::NotificationStyle};
+
 pub struct Client {
     pub serenity_client: serenity::Client,
 }
@@ -56,3 +59,162 @@ pub async fn get_event_channel_id(
     .and_then(|record| Some(ChannelId::from(record.channel_id.parse::<u64>().ok()?)));
     Ok(response)
 }
+
+pub async fn set_guild_time_zone(
+    pool: &PgPool,
+    guild_id: &GuildId,
+    time_zone: &Tz,
+) -> Result<(), crate::Error> {
+    query!(
+        "
+        INSERT INTO guild_settings(guild_id, time_zone)
+        VALUES($1, $2)
+        ON CONFLICT (guild_id) DO UPDATE SET time_zone = EXCLUDED.time_zone
+        ",
+        guild_id.get().to_string(),
+        time_zone.name(),
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// The time zone of the server, UTC unless set.
+pub async fn get_guild_time_zone(pool: &PgPool, guild_id: &GuildId) -> Result<Tz, crate::Error> {
+    let response = query!(
+        "
+        SELECT time_zone FROM guild_settings
+        WHERE guild_id = $1
+        ",
+        guild_id.get().to_string()
+    )
+    .fetch_optional(pool)
+    .await?
+    .and_then(|record| record.time_zone.parse().ok())
+    .unwrap_or(Tz::UTC);
+    Ok(response)
+}
+
+pub async fn set_guild_reminder_days(
+    pool: &PgPool,
+    guild_id: &GuildId,
+    reminder_days: &[u32],
+) -> Result<(), crate::Error> {
+    let reminder_days = reminder_days
+        .iter()
+        .map(|days| *days as i32)
+        .collect::<Vec<_>>();
+    query!(
+        "
+        INSERT INTO guild_settings(guild_id, reminder_days)
+        VALUES($1, $2)
+        ON CONFLICT (guild_id) DO UPDATE SET reminder_days = EXCLUDED.reminder_days
+        ",
+        guild_id.get().to_string(),
+        &reminder_days,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Days before an occurrence to remind about it, only the day itself unless set.
+pub async fn get_guild_reminder_days(
+    pool: &PgPool,
+    guild_id: &GuildId,
+) -> Result<Vec<u32>, crate::Error> {
+    let response = query!(
+        "
+        SELECT reminder_days FROM guild_settings
+        WHERE guild_id = $1
+        ",
+        guild_id.get().to_string()
+    )
+    .fetch_optional(pool)
+    .await?
+    .map(|record| {
+        record
+            .reminder_days
+            .into_iter()
+            .filter_map(|days| u32::try_from(days).ok())
+            .collect()
+    })
+    .unwrap_or_else(|| vec![0]);
+    Ok(response)
+}
+
+pub async fn set_guild_notification_style(
+    pool: &PgPool,
+    guild_id: &GuildId,
+    style: NotificationStyle,
+) -> Result<(), crate::Error> {
+    query!(
+        "
+        INSERT INTO guild_settings(guild_id, notification_style)
+        VALUES($1, $2)
+        ON CONFLICT (guild_id) DO UPDATE SET notification_style = EXCLUDED.notification_style
+        ",
+        guild_id.get().to_string(),
+        style.as_str(),
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// How the server notifications are posted, embeds unless set.
+pub async fn get_guild_notification_style(
+    pool: &PgPool,
+    guild_id: &GuildId,
+) -> Result<NotificationStyle, crate::Error> {
+    let response = query!(
+        "
+        SELECT notification_style FROM guild_settings
+        WHERE guild_id = $1
+        ",
+        guild_id.get().to_string()
+    )
+    .fetch_optional(pool)
+    .await?
+    .and_then(|record| NotificationStyle::parse(&record.notification_style).ok())
+    .unwrap_or_default();
+    Ok(response)
+}
+
+pub async fn set_guild_date_format(
+    pool: &PgPool,
+    guild_id: &GuildId,
+    format: DateFormat,
+) -> Result<(), crate::Error> {
+    query!(
+        "
+        INSERT INTO guild_settings(guild_id, date_format)
+        VALUES($1, $2)
+        ON CONFLICT (guild_id) DO UPDATE SET date_format = EXCLUDED.date_format
+        ",
+        guild_id.get().to_string(),
+        format.as_str(),
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// How the server writes the dates, ISO unless set.
+pub async fn get_guild_date_format(
+    pool: &PgPool,
+    guild_id: &GuildId,
+) -> Result<DateFormat, crate::Error> {
+    let response = query!(
+        "
+        SELECT date_format FROM guild_settings
+        WHERE guild_id = $1
+        ",
+        guild_id.get().to_string()
+    )
+    .fetch_optional(pool)
+    .await?
+    .and_then(|record| DateFormat::parse(&record.date_format).ok())
+    .unwrap_or_default();
+    Ok(response)
+}
@@ -1,11 +1,319 @@
-use crate::Error;
+use chrono::NaiveDate;
+use google_calendar3::api::Event;
+use serenity::all::{
+    CommandOptionType, CreateCommand, CreateCommandOption, ResolvedOption, ResolvedValue, RoleId,
+    UserId,
+};
+
+use crate::{
+    calendar::{
+        remove_event_property, set_event_property, set_event_url, RecurrenceOptions, Repeat,
+        MENTION_ROLES_PROPERTY, MENTION_USERS_PROPERTY,
+    },
+    dates::{parse_date, DateFormat},
+    Error,
+};
+use url::Url;
 
 pub mod create_calendar;
 pub mod create_event;
 pub mod delete_calendar;
 pub mod delete_event;
+pub mod edit_event;
 pub mod list_events;
 pub mod ping;
+pub mod set_date_format;
 pub mod set_event_channel;
+pub mod set_notification_style;
+pub mod set_reminders;
+pub mod set_template;
+pub mod set_timezone;
 
 pub type MessageResult = Result<String, Error>;
+
+/// Leaves room for the template in the notifications.
+const MAX_DESCRIPTION_LENGTH: u16 = 500;
+/// Keeps the event lines of `/list_events` short enough for a page.
+const MAX_LOCATION_LENGTH: u16 = 100;
+const MAX_URL_LENGTH: u16 = 200;
+/// The options of the members pinged by the notifications.
+const MENTION_USER_OPTIONS: [&str; 3] = ["user", "user_2", "user_3"];
+
+/// Finds a string option by its name, optional options may be skipped by the user.
+pub fn get_string_option<'a>(options: &[ResolvedOption<'a>], name: &str) -> Option<&'a str> {
+    options.iter().find_map(|option| match option {
+        ResolvedOption {
+            name: option_name,
+            value: ResolvedValue::String(value),
+            ..
+        } if *option_name == name => Some(*value),
+        _ => None,
+    })
+}
+
+pub fn get_bool_option(options: &[ResolvedOption<'_>], name: &str) -> Option<bool> {
+    options.iter().find_map(|option| match option {
+        ResolvedOption {
+            name: option_name,
+            value: ResolvedValue::Boolean(value),
+            ..
+        } if *option_name == name => Some(*value),
+        _ => None,
+    })
+}
+
+pub fn get_integer_option(options: &[ResolvedOption<'_>], name: &str) -> Option<i64> {
+    options.iter().find_map(|option| match option {
+        ResolvedOption {
+            name: option_name,
+            value: ResolvedValue::Integer(value),
+            ..
+        } if *option_name == name => Some(*value),
+        _ => None,
+    })
+}
+
+pub fn get_role_option(options: &[ResolvedOption<'_>], name: &str) -> Option<RoleId> {
+    options.iter().find_map(|option| match option {
+        ResolvedOption {
+            name: option_name,
+            value: ResolvedValue::Role(role),
+            ..
+        } if *option_name == name => Some(role.id),
+        _ => None,
+    })
+}
+
+pub fn get_user_option(options: &[ResolvedOption<'_>], name: &str) -> Option<UserId> {
+    options.iter().find_map(|option| match option {
+        ResolvedOption {
+            name: option_name,
+            value: ResolvedValue::User(user, _),
+            ..
+        } if *option_name == name => Some(user.id),
+        _ => None,
+    })
+}
+
+/// Reads the `repeat`, `interval`, `count`, `until` and `rrule` options.
+pub fn get_recurrence_options<'a>(
+    options: &[ResolvedOption<'a>],
+    today: NaiveDate,
+    date_format: DateFormat,
+) -> Result<RecurrenceOptions<'a>, Error> {
+    Ok(RecurrenceOptions {
+        repeat: get_string_option(options, "repeat")
+            .map(Repeat::parse)
+            .transpose()?,
+        interval: get_integer_option(options, "interval"),
+        count: get_integer_option(options, "count"),
+        until: get_string_option(options, "until")
+            .map(|until| parse_date(until, today, date_format))
+            .transpose()?,
+        rule: get_string_option(options, "rrule"),
+    })
+}
+
+/// Registers the options read by [`get_recurrence_options`].
+pub fn add_recurrence_options(command: CreateCommand) -> CreateCommand {
+    let repeat = Repeat::CHOICES.into_iter().fold(
+        CreateCommandOption::new(
+            CommandOptionType::String,
+            "repeat",
+            "How often the event repeats",
+        ),
+        |option, (name, value)| option.add_string_choice(name, value),
+    );
+    command
+        .add_option(repeat.required(false))
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "interval",
+                "Repeats every this many days, weeks, months or years",
+            )
+            .min_int_value(1)
+            .max_int_value(999)
+            .required(false),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "count",
+                "How many times the event happens",
+            )
+            .min_int_value(1)
+            .max_int_value(999)
+            .required(false),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "until",
+                "The last date the event may happen on, e.g. 31 dec, in 3 months or the server date format",
+            )
+            .required(false),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "rrule",
+                "A custom RRULE replacing the repeat options, e.g. FREQ=WEEKLY;BYDAY=FR",
+            )
+            .required(false),
+        )
+}
+
+/// The `description`, `location` and `url` options, an empty one removes the field.
+#[derive(Debug, Default)]
+pub struct EventDetails<'a> {
+    pub description: Option<&'a str>,
+    pub location: Option<&'a str>,
+    pub url: Option<&'a str>,
+}
+
+impl EventDetails<'_> {
+    pub fn is_empty(&self) -> bool {
+        self.description.is_none() && self.location.is_none() && self.url.is_none()
+    }
+
+    pub fn apply(&self, event: &mut Event) -> Result<(), Error> {
+        let non_empty = |value: &str| {
+            let value = value.trim();
+            (!value.is_empty()).then(|| value.to_string())
+        };
+        if let Some(description) = self.description {
+            event.description = non_empty(description);
+        }
+        if let Some(location) = self.location {
+            event.location = non_empty(location);
+        }
+        if let Some(url) = self.url {
+            let url = non_empty(url)
+                .map(|url| parse_http_url(&url).ok_or(Error::InvalidUrl(url)))
+                .transpose()?;
+            set_event_url(event, url.map(Into::into));
+        }
+        Ok(())
+    }
+}
+
+pub fn get_event_details<'a>(options: &[ResolvedOption<'a>]) -> EventDetails<'a> {
+    EventDetails {
+        description: get_string_option(options, "description"),
+        location: get_string_option(options, "location"),
+        url: get_string_option(options, "url"),
+    }
+}
+
+/// Registers the options read by [`get_event_details`].
+pub fn add_event_details_options(command: CreateCommand) -> CreateCommand {
+    command
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "description",
+                "The description shown in the notifications, a space removes it",
+            )
+            .max_length(MAX_DESCRIPTION_LENGTH)
+            .required(false),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "location",
+                "Where the event takes place, a space removes it",
+            )
+            .max_length(MAX_LOCATION_LENGTH)
+            .required(false),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "url",
+                "A link to the event page, stream or tickets, a space removes it",
+            )
+            .max_length(MAX_URL_LENGTH)
+            .required(false),
+        )
+}
+
+/// Only the web links are accepted, the notifications can't open the other ones.
+pub fn parse_http_url(input: &str) -> Option<Url> {
+    Url::parse(input.trim())
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+}
+
+/// The `role` and the `user` options pinged by the notifications, the set ones replace the
+/// event ones.
+#[derive(Debug, Default)]
+pub struct EventMentions {
+    pub role: Option<RoleId>,
+    pub users: Vec<UserId>,
+}
+
+impl EventMentions {
+    pub fn is_empty(&self) -> bool {
+        self.role.is_none() && self.users.is_empty()
+    }
+
+    pub fn apply(&self, event: &mut Event) {
+        if let Some(role_id) = self.role {
+            set_event_property(event, MENTION_ROLES_PROPERTY, role_id.to_string());
+        }
+        if !self.users.is_empty() {
+            let users = self
+                .users
+                .iter()
+                .map(UserId::to_string)
+                .collect::<Vec<_>>()
+                .join(",");
+            set_event_property(event, MENTION_USERS_PROPERTY, users);
+        }
+    }
+}
+
+/// Stops the notifications of the event from pinging anyone.
+pub fn clear_event_mentions(event: &mut Event) {
+    remove_event_property(event, MENTION_ROLES_PROPERTY);
+    remove_event_property(event, MENTION_USERS_PROPERTY);
+}
+
+pub fn get_event_mentions(options: &[ResolvedOption<'_>]) -> EventMentions {
+    let mut users = Vec::new();
+    for user_id in MENTION_USER_OPTIONS
+        .iter()
+        .filter_map(|name| get_user_option(options, name))
+    {
+        if !users.contains(&user_id) {
+            users.push(user_id);
+        }
+    }
+    EventMentions {
+        role: get_role_option(options, "role"),
+        users,
+    }
+}
+
+/// Registers the options read by [`get_event_mentions`].
+pub fn add_mention_options(command: CreateCommand) -> CreateCommand {
+    let command = command.add_option(
+        CreateCommandOption::new(
+            CommandOptionType::Role,
+            "role",
+            "The role pinged by the notifications",
+        )
+        .required(false),
+    );
+    MENTION_USER_OPTIONS.iter().fold(command, |command, name| {
+        command.add_option(
+            CreateCommandOption::new(
+                CommandOptionType::User,
+                *name,
+                "A member pinged by the notifications, e.g. the birthday person",
+            )
+            .required(false),
+        )
+    })
+}
@@ -1,9 +1,10 @@
-use crate::{calendar::Client as CalendarClient, Error};
+use crate::{
+    calendar::{get_guild_calendar_id, set_guild_calendar, Backend},
+    Error, Pool,
+};
 use serenity::all::{Context, CreateCommand, GuildId, Permissions, ResolvedOption};
 use tracing::{info, instrument};
 
-use crate::calendar::get_calendar_url;
-
 use super::MessageResult;
 
 #[instrument]
@@ -14,27 +15,21 @@ pub async fn run(
 ) -> MessageResult {
     info!("Creating a calendar");
     let lock = ctx.data.read().await;
-    let calendar_client = lock
-        .get::<CalendarClient>()
-        .ok_or(Error::NoCalendarClient)?;
-    let calendars = calendar_client.get_calendars_by_guild_id(&guild_id).await?;
-    if calendars.is_some() {
-        return Ok(format!(
-            "A calendar already exists: {}",
-            calendars
-                .into_iter()
-                .map(|calendar| {
-                    let calendar_id = calendar.id.expect("No calendar id");
-                    get_calendar_url(&calendar_id)
-                })
-                .collect::<Vec<_>>()
-                .join(", ")
-        ));
+    let backend = lock.get::<Backend>().ok_or(Error::NoCalendarClient)?;
+    let pool = lock.get::<Pool>().ok_or(Error::NoPool)?;
+    if let Some(calendar_id) = get_guild_calendar_id(pool, &guild_id).await? {
+        return Ok(match backend.calendar_url(&calendar_id) {
+            Some(url) => format!("A calendar already exists: {url}"),
+            None => "A calendar already exists!".into(),
+        });
     }
-    calendar_client
-        .create_calendar(&guild_id.to_string())
-        .await?;
-    Ok("Created the calendar! `/list_events` to get the url".into())
+    let calendar = backend.create_calendar(&guild_id.to_string()).await?;
+    let calendar_id = calendar.id.ok_or(Error::MissingCalendarId)?;
+    set_guild_calendar(pool, &guild_id, &calendar_id).await?;
+    Ok(match backend.calendar_url(&calendar_id) {
+        Some(_) => "Created the calendar! `/list_events` to get the url".into(),
+        None => "Created the calendar!".into(),
+    })
 }
 
 pub fn register() -> CreateCommand {
@@ -1,7 +1,16 @@
-use std::str::FromStr;
-
-use crate::{calendar::Client as CalendarClient, Error};
-use chrono::{Datelike, Days, NaiveDate, Utc};
+use crate::{
+    calendar::{
+        build_recurrence, format_occurrence, format_recurrence, get_guild_calendar_id,
+        set_event_property, Backend, EventStart, Repeat, CATEGORY_PROPERTY, IMAGE_URL_PROPERTY,
+        REMINDER_DAYS_PROPERTY,
+    },
+    dates::{describe_parsed_date, parse_date, DateFormat},
+    discord::{get_guild_date_format, get_guild_time_zone, LabelCache},
+    notifier::{format_reminder_days, parse_reminder_days},
+    Error, Pool,
+};
+use chrono::{Duration, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
 use google_calendar3::api::{Event, EventDateTime};
 use serenity::all::{
     CommandOptionType, Context, CreateCommand, CreateCommandOption, GuildId, Permissions,
@@ -9,7 +18,15 @@ use serenity::all::{
 };
 use tracing::{instrument, warn};
 
-use super::MessageResult;
+use super::{
+    add_event_details_options, add_mention_options, add_recurrence_options, get_event_details,
+    get_event_mentions, get_recurrence_options, get_string_option, parse_http_url, MessageResult,
+};
+
+/// Timed events are created an hour long.
+const EVENT_DURATION_HOURS: i64 = 1;
+/// The longest event, a year long festival at most.
+const MAX_EVENT_DAYS: i64 = 366;
 
 #[instrument]
 pub async fn run(
@@ -25,56 +42,169 @@ pub async fn run(
         return Err(Error::MissingParameter("label".into()));
     };
 
-    let date = match options.get(1) {
-        Some(ResolvedOption {
-            value: ResolvedValue::String(date),
-            ..
-        }) => {
-            let year = Utc::now().year();
-            let date = format!("{year}-{date}");
-            NaiveDate::from_str(&date)?
+    let lock = ctx.data.read().await;
+    let backend = lock.get::<Backend>().ok_or(Error::NoCalendarClient)?;
+    let pool = lock.get::<Pool>().ok_or(Error::NoPool)?;
+    let time_zone = get_guild_time_zone(pool, guild_id).await?;
+    let date_format = get_guild_date_format(pool, guild_id).await?;
+    let today = Utc::now().with_timezone(&time_zone).date_naive();
+
+    let date_input = get_string_option(options, "date");
+    let start_date = match date_input {
+        Some(date) => parse_date(date, today, date_format)?,
+        None => today,
+    };
+
+    let start = match get_string_option(options, "time") {
+        Some(time) => {
+            let date_time = start_date.and_time(NaiveTime::parse_from_str(time, "%H:%M")?);
+            let date_time = time_zone
+                .from_local_datetime(&date_time)
+                .earliest()
+                .ok_or(Error::NonexistentLocalTime(date_time))?;
+            EventStart::Timed(date_time.with_timezone(&Utc))
         }
-        _ => Utc::now().date_naive(),
+        None => EventStart::AllDay(start_date),
+    };
+
+    let end = event_end(
+        start,
+        get_string_option(options, "end_date"),
+        get_string_option(options, "duration"),
+        &time_zone,
+        date_format,
+    )?;
+    let (event_start, event_end) = match (start, end) {
+        (EventStart::Timed(start), EventStart::Timed(end)) => (
+            EventDateTime {
+                date_time: Some(start),
+                time_zone: Some(time_zone.name().into()),
+                ..Default::default()
+            },
+            EventDateTime {
+                date_time: Some(end),
+                time_zone: Some(time_zone.name().into()),
+                ..Default::default()
+            },
+        ),
+        (start, end) => (
+            EventDateTime {
+                date: Some(start.local_date(&time_zone)),
+                ..Default::default()
+            },
+            EventDateTime {
+                date: Some(end.local_date(&time_zone)),
+                ..Default::default()
+            },
+        ),
     };
 
-    let event = Event {
+    let mut event = Event {
         summary: Some(label.to_string()),
-        start: Some(EventDateTime {
-            date: Some(date),
-            ..Default::default()
-        }),
-        end: Some(EventDateTime {
-            date: Some(
-                date.checked_add_days(Days::new(1))
-                    .expect("Out of range days"),
-            ),
-            ..Default::default()
-        }),
-        recurrence: Some(vec!["RRULE:FREQ=YEARLY".into()]),
+        start: Some(event_start),
+        end: Some(event_end),
         ..Default::default()
     };
+    let mut recurrence = get_recurrence_options(options, today, date_format)?;
+    if recurrence.repeat.is_none() && recurrence.rule.is_none() {
+        // Birthdays and anniversaries come back every year
+        recurrence.repeat = Some(Repeat::Yearly);
+    }
+    event.recurrence = build_recurrence(&event, &recurrence)?;
+    if let Some(days) = get_string_option(options, "reminders") {
+        let reminder_days = parse_reminder_days(days)?;
+        set_event_property(
+            &mut event,
+            REMINDER_DAYS_PROPERTY,
+            format_reminder_days(&reminder_days),
+        );
+    }
+    if let Some(category) = get_string_option(options, "category") {
+        set_event_property(&mut event, CATEGORY_PROPERTY, category.trim().into());
+    }
+    get_event_mentions(options).apply(&mut event);
+    if let Some(image_url) = get_string_option(options, "image") {
+        let url = parse_http_url(image_url).ok_or(Error::InvalidImageUrl(image_url.into()))?;
+        set_event_property(&mut event, IMAGE_URL_PROPERTY, url.into());
+    }
+    get_event_details(options).apply(&mut event)?;
 
-    let lock = ctx.data.read().await;
-    let calendar_client = lock
-        .get::<CalendarClient>()
-        .ok_or(Error::NoCalendarClient)?;
-
-    let calendars = calendar_client.get_calendars_by_guild_id(guild_id).await?;
-    let Some(calendar) = calendars else {
+    let Some(calendar_id) = get_guild_calendar_id(pool, guild_id).await? else {
         warn!("Couldn't find a calendar for the guild");
         return Ok("No calendar for the server, create a new one! `/create_calendar`".into());
     };
 
-    let calendar_id = calendar.id.expect("No calendar id");
-    calendar_client.create_event(event, &calendar_id).await?;
+    let event = backend.create_event(event, &calendar_id).await?;
+    if let Some(labels) = lock.get::<LabelCache>() {
+        labels.invalidate(guild_id).await;
+    }
+
+    let date = format_occurrence(&event, start, &time_zone, date_format);
+    let repeats = format_recurrence(&event, date_format).unwrap_or_else(|| "once".into());
+    let mut message =
+        format!("The event \"{label}\" was created successfully! Date: {date}, repeats {repeats}");
+    if let Some(parsed) =
+        date_input.and_then(|input| describe_parsed_date(input, start_date, date_format))
+    {
+        message = format!("{message}\n{parsed}");
+    }
+    Ok(message)
+}
+
+/// Where the event ends, the day after an all-day event or an hour after a timed one unless the
+/// last date or the duration is set. The all-day events end at the start of a date.
+fn event_end(
+    start: EventStart,
+    end_date: Option<&str>,
+    duration: Option<&str>,
+    time_zone: &Tz,
+    date_format: DateFormat,
+) -> Result<EventStart, Error> {
+    let start_date = start.local_date(time_zone);
+    let duration = match (end_date, duration) {
+        (Some(_), Some(_)) => {
+            return Err(Error::InvalidEventEnd(
+                "set either the end date or the duration".into(),
+            ))
+        }
+        (Some(end_date), None) => {
+            let days = (parse_date(end_date, start_date, date_format)? - start_date).num_days();
+            match start {
+                EventStart::AllDay(_) => Duration::days(days + 1),
+                // A timed event ends at its start time on the last date
+                EventStart::Timed(_) => Duration::days(days),
+            }
+        }
+        (None, Some(duration)) => humantime::parse_duration(duration)
+            .ok()
+            .and_then(|duration| Duration::from_std(duration).ok())
+            .ok_or_else(|| {
+                Error::InvalidEventEnd(format!("{duration}, use a duration like 3days or 2h 30m"))
+            })?,
+        (None, None) => match start {
+            EventStart::AllDay(_) => Duration::days(1),
+            EventStart::Timed(_) => Duration::hours(EVENT_DURATION_HOURS),
+        },
+    };
+    if duration <= Duration::zero() || duration > Duration::days(MAX_EVENT_DAYS) {
+        return Err(Error::InvalidEventEnd(format!(
+            "the event must end after it starts and last up to {MAX_EVENT_DAYS} days"
+        )));
+    }
 
-    Ok(format!(
-        "The event \"{label}\" was created successfully! Date: {date}"
-    ))
+    match start {
+        EventStart::AllDay(date) if duration == Duration::days(duration.num_days()) => {
+            Ok(EventStart::AllDay(date + duration))
+        }
+        EventStart::AllDay(_) => Err(Error::InvalidEventEnd(
+            "all-day events last whole days, set the time for a shorter one".into(),
+        )),
+        EventStart::Timed(date_time) => Ok(EventStart::Timed(date_time + duration)),
+    }
 }
 
 pub fn register() -> CreateCommand {
-    CreateCommand::new("create_event")
+    let command = CreateCommand::new("create_event")
         .description("Create an event with a label on a specific date")
         .add_option(
             CreateCommandOption::new(CommandOptionType::String, "label", "The label of the event")
@@ -84,9 +214,58 @@ pub fn register() -> CreateCommand {
             CreateCommandOption::new(
                 CommandOptionType::String,
                 "date",
-                "The date of the event using the MM-DD format. Default is today",
+                "The date of the event, e.g. 25 dec, next friday, in 3 days or the server format. Default is today",
+            )
+            .required(false),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "time",
+                "The start time of the event using the HH:MM format in the server time zone. Default is all-day",
+            )
+            .required(false),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "end_date",
+                "The last date of a multi-day event, e.g. 27 dec, next sunday or the server date format",
+            )
+            .required(false),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "duration",
+                "How long the event lasts, e.g. 3days or 2h 30m. Default is a day or an hour",
+            )
+            .required(false),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "reminders",
+                "Comma separated days before the event to remind, e.g. 7,1,0. Default is the server ones",
+            )
+            .required(false),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "image",
+                "The url of an image shown in the notifications",
+            )
+            .required(false),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "category",
+                "The category picking the notification templates, e.g. birthday",
             )
             .required(false),
         )
-        .default_member_permissions(Permissions::ADMINISTRATOR)
+        .default_member_permissions(Permissions::ADMINISTRATOR);
+    add_recurrence_options(add_event_details_options(add_mention_options(command)))
 }
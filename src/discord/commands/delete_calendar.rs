@@ -1,4 +1,7 @@
-use crate::{calendar::Client as CalendarClient, Error};
+use crate::{
+    calendar::{delete_guild_calendar, get_guild_calendar_id, Backend},
+    Error, Pool,
+};
 use serenity::all::{Context, CreateCommand, GuildId, Permissions, ResolvedOption};
 use tracing::{info, instrument};
 
@@ -12,18 +15,13 @@ pub async fn run(
 ) -> MessageResult {
     info!("Deleting calendars");
     let lock = ctx.data.read().await;
-    let calendar_client = lock
-        .get::<CalendarClient>()
-        .ok_or(Error::NoCalendarClient)?;
-    let calendars = calendar_client.get_calendars_by_guild_id(&guild_id).await?;
+    let backend = lock.get::<Backend>().ok_or(Error::NoCalendarClient)?;
+    let pool = lock.get::<Pool>().ok_or(Error::NoPool)?;
 
-    let mut handles = vec![];
-    for calendar in &calendars {
-        let calendar_id = calendar.id.as_ref().expect("No calendar id");
-        let handle = calendar_client.delete_calendar(calendar_id);
-        handles.push(handle);
+    if let Some(calendar_id) = get_guild_calendar_id(pool, &guild_id).await? {
+        backend.delete_calendar(&calendar_id).await?;
+        delete_guild_calendar(pool, &guild_id).await?;
     }
-    futures::future::join_all(handles).await;
 
     Ok("Deleted calendars!".into())
 }
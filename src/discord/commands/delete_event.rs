@@ -1,4 +1,8 @@
-use crate::{calendar::Client as CalendarClient, Error};
+use crate::{
+    calendar::{get_guild_calendar_id, Backend},
+    discord::LabelCache,
+    Error, Pool,
+};
 use serenity::all::{
     CommandOptionType, Context, CreateCommand, CreateCommandOption, GuildId, Permissions,
     ResolvedOption, ResolvedValue,
@@ -22,27 +26,25 @@ pub async fn run(
     };
 
     let lock = ctx.data.read().await;
-    let calendar_client = lock
-        .get::<CalendarClient>()
-        .ok_or(Error::NoCalendarClient)?;
-    let calendars = calendar_client.get_calendars_by_guild_id(guild_id).await?;
-    let Some(calendar) = calendars else {
+    let backend = lock.get::<Backend>().ok_or(Error::NoCalendarClient)?;
+    let pool = lock.get::<Pool>().ok_or(Error::NoPool)?;
+    let Some(calendar_id) = get_guild_calendar_id(pool, guild_id).await? else {
         warn!("Couldn't find a calendar for the guild");
         return Ok("No calendar for the server, create a new one! `/create_calendar`".into());
     };
-    let calendar_id = calendar.id.expect("No calendar id");
 
-    let event_ids = calendar_client
-        .get_event_id_by_label(label, &calendar_id)
-        .await?;
+    let event_ids = backend.get_event_id_by_label(label, &calendar_id).await?;
     info!(?event_ids, "Deleting these events");
 
     let mut handles = Vec::with_capacity(event_ids.len());
     for id in &event_ids {
-        let handle = calendar_client.delete_event(id, &calendar_id);
+        let handle = backend.delete_event(id, &calendar_id);
         handles.push(handle);
     }
     futures::future::join_all(handles).await;
+    if let Some(labels) = lock.get::<LabelCache>() {
+        labels.invalidate(guild_id).await;
+    }
 
     Ok(format!(
         "Deleted {} events from the calendar!",
@@ -55,6 +57,7 @@ pub fn register() -> CreateCommand {
         .description("Delete events with the specified label")
         .add_option(
             CreateCommandOption::new(CommandOptionType::String, "label", "The label of the event")
+                .set_autocomplete(true)
                 .required(true),
         )
         .default_member_permissions(Permissions::ADMINISTRATOR)
@@ -0,0 +1,196 @@
+use chrono::{Duration, NaiveDate, TimeZone, Utc};
+use google_calendar3::api::{Event, EventDateTime};
+use serenity::all::{
+    CommandOptionType, Context, CreateCommand, CreateCommandOption, GuildId, Permissions,
+    ResolvedOption,
+};
+use tracing::{info, instrument, warn};
+
+use crate::{
+    calendar::{
+        build_recurrence, event_time_zone, format_occurrence, format_recurrence,
+        get_guild_calendar_id, validate_recurrence, Backend, EventStart,
+    },
+    dates::{describe_parsed_date, parse_date},
+    discord::{get_guild_date_format, get_guild_time_zone, LabelCache},
+    Error, Pool,
+};
+
+use super::{
+    add_event_details_options, add_mention_options, add_recurrence_options, clear_event_mentions,
+    get_bool_option, get_event_details, get_event_mentions, get_recurrence_options,
+    get_string_option, MessageResult,
+};
+
+#[instrument]
+pub async fn run(
+    ctx: &Context,
+    guild_id: &GuildId,
+    options: &[ResolvedOption<'_>],
+) -> MessageResult {
+    let Some(label) = get_string_option(options, "label") else {
+        return Err(Error::MissingParameter("label".into()));
+    };
+    let new_label = get_string_option(options, "new_label");
+    let date = get_string_option(options, "date");
+    let details = get_event_details(options);
+    let mentions = get_event_mentions(options);
+    let clear_mentions = get_bool_option(options, "clear_mentions").unwrap_or_default();
+
+    let lock = ctx.data.read().await;
+    let backend = lock.get::<Backend>().ok_or(Error::NoCalendarClient)?;
+    let pool = lock.get::<Pool>().ok_or(Error::NoPool)?;
+    let time_zone = get_guild_time_zone(pool, guild_id).await?;
+    let date_format = get_guild_date_format(pool, guild_id).await?;
+    let today = Utc::now().with_timezone(&time_zone).date_naive();
+    let recurrence = get_recurrence_options(options, today, date_format)?;
+    if new_label.is_none()
+        && date.is_none()
+        && recurrence.is_empty()
+        && details.is_empty()
+        && mentions.is_empty()
+        && !clear_mentions
+    {
+        return Ok(
+            "Nothing to change, set a new label, date, repeat, description, location, url or mentions!"
+                .into(),
+        );
+    }
+    let Some(calendar_id) = get_guild_calendar_id(pool, guild_id).await? else {
+        warn!("Couldn't find a calendar for the guild");
+        return Ok("No calendar for the server, create a new one! `/create_calendar`".into());
+    };
+
+    let mut events = backend
+        .list_events(&calendar_id)
+        .await?
+        .into_iter()
+        .filter(|event| event.summary.as_deref() == Some(label));
+    let (Some(mut event), None) = (events.next(), events.next()) else {
+        return Ok(format!(
+            "Couldn't pick the event, there must be exactly one labeled \"{label}\"!"
+        ));
+    };
+
+    if let Some(new_label) = new_label {
+        event.summary = Some(new_label.into());
+    }
+    details.apply(&mut event)?;
+    if clear_mentions {
+        clear_event_mentions(&mut event);
+    }
+    mentions.apply(&mut event);
+    let mut parsed_date = None;
+    if let Some(date) = date {
+        let new_date = parse_date(date, today, date_format)?;
+        move_event(&mut event, new_date)?;
+        parsed_date = describe_parsed_date(date, new_date, date_format);
+    }
+    if !recurrence.is_empty() {
+        event.recurrence = build_recurrence(&event, &recurrence)?;
+    }
+    if date.is_some() || !recurrence.is_empty() {
+        // The rule may not fit the new start, e.g. its until is before it
+        validate_recurrence(&event)?;
+    }
+
+    info!(?event, "Updating the event");
+    let event = backend.update_event(event, &calendar_id).await?;
+    if let Some(labels) = lock.get::<LabelCache>() {
+        labels.invalidate(guild_id).await;
+    }
+    let start = EventStart::from_event(&event).ok_or(Error::MissingParameter("start".into()))?;
+    let repeats = format_recurrence(&event, date_format).unwrap_or_else(|| "once".into());
+    let mut message = format!(
+        "The event \"{}\" was updated successfully! Date: {}, repeats {repeats}",
+        event.summary.as_deref().unwrap_or(label),
+        format_occurrence(&event, start, &time_zone, date_format)
+    );
+    if let Some(parsed_date) = parsed_date {
+        message = format!("{message}\n{parsed_date}");
+    }
+    Ok(message)
+}
+
+/// Moves the event to the date keeping its local start time and its duration.
+fn move_event(event: &mut Event, date: NaiveDate) -> Result<(), Error> {
+    let start = EventStart::from_event(event).ok_or(Error::MissingParameter("start".into()))?;
+    let end = event.end.as_ref();
+    match start {
+        EventStart::AllDay(start_date) => {
+            let days = end
+                .and_then(|end| end.date)
+                .map(|end_date| end_date - start_date)
+                .unwrap_or(Duration::days(1));
+            event.start = Some(EventDateTime {
+                date: Some(date),
+                ..Default::default()
+            });
+            event.end = Some(EventDateTime {
+                date: Some(date + days),
+                ..Default::default()
+            });
+        }
+        EventStart::Timed(start_date_time) => {
+            let duration = end
+                .and_then(|end| end.date_time)
+                .map(|end_date_time| end_date_time - start_date_time)
+                .unwrap_or(Duration::hours(1));
+            let time_zone = event_time_zone(event);
+            let local = date.and_time(start_date_time.with_timezone(&time_zone).time());
+            let start_date_time = time_zone
+                .from_local_datetime(&local)
+                .earliest()
+                .ok_or(Error::NonexistentLocalTime(local))?
+                .with_timezone(&Utc);
+            let time_zone = Some(time_zone.name().to_string());
+            event.start = Some(EventDateTime {
+                date_time: Some(start_date_time),
+                time_zone: time_zone.clone(),
+                ..Default::default()
+            });
+            event.end = Some(EventDateTime {
+                date_time: Some(start_date_time + duration),
+                time_zone,
+                ..Default::default()
+            });
+        }
+    }
+    Ok(())
+}
+
+pub fn register() -> CreateCommand {
+    let command = CreateCommand::new("edit_event")
+        .description("Change an event keeping its id")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "label",
+                "The label of the event to change",
+            )
+            .set_autocomplete(true)
+            .required(true),
+        )
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::String, "new_label", "The new label")
+                .required(false),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "date",
+                "The new date, e.g. 25 dec, next friday or the server date format, the start time is kept",
+            )
+            .required(false),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Boolean,
+                "clear_mentions",
+                "Stop pinging the event role and members, the role and users set with it are kept",
+            )
+            .required(false),
+        )
+        .default_member_permissions(Permissions::ADMINISTRATOR);
+    add_recurrence_options(add_event_details_options(add_mention_options(command)))
+}
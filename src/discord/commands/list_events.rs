@@ -1,71 +1,510 @@
-use crate::{calendar::Client as CalendarClient, Error};
-use serenity::all::{Context, CreateCommand, GuildId, ResolvedOption};
+use crate::{
+    calendar::{
+        event_url, format_occurrence, format_recurrence, get_guild_calendar_id, next_occurrence,
+        Backend, EventStart,
+    },
+    dates::{parse_date, DateFormat},
+    discord::{get_guild_date_format, get_guild_time_zone},
+    text::shorten,
+    Error, Pool,
+};
+use chrono::{DateTime, Datelike, Days, Duration, Months, NaiveDate, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use google_calendar3::api::Event;
+use serenity::all::{
+    ButtonStyle, CommandInteraction, CommandOptionType, ComponentInteraction, Context,
+    CreateActionRow, CreateAttachment, CreateButton, CreateCommand, CreateCommandOption,
+    CreateInteractionResponse, CreateInteractionResponseMessage, GuildId, ResolvedOption,
+};
 use tracing::{info, instrument, warn};
 
-use crate::calendar::get_calendar_url;
+use super::{get_bool_option, get_integer_option, get_string_option};
 
-use super::MessageResult;
+/// The most events shown on a page, fewer when they don't fit the message.
+const PAGE_SIZE: usize = 10;
+/// Discord rejects the longer messages.
+const MAX_MESSAGE_LENGTH: usize = 2000;
+/// The page lines are cut, so a few long labels still fit on a page, the download keeps them.
+const MAX_LINE_LENGTH: usize = 300;
+/// Room for the ` (page 1/2):` of the page header.
+const PAGE_HEADER_LENGTH: usize = 30;
+/// The buttons carry the page they open and the filter, so they work whatever the bot remembers.
+pub const PAGE_BUTTON_PREFIX: &str = "list_events:page:";
+/// Keeps the button ids under the 100 characters Discord allows.
+const MAX_SEARCH_LENGTH: u16 = 50;
+const MAX_DAYS: u64 = 366;
+/// The characters of the description and the location shown under an event.
+const MAX_DETAIL_LENGTH: usize = 60;
+const NO_CALENDAR: &str =
+    "No calendar found for the server! Create a new one using `/create_calendar`";
 
-#[instrument]
+/// The listed events of the server.
+struct EventList {
+    lines: Vec<String>,
+    calendar_url: Option<String>,
+    date_format: DateFormat,
+}
+
+/// What the listed events are narrowed to.
+#[derive(Debug, Default)]
+struct EventFilter {
+    /// The first date of the range, the events without an occurrence since then are hidden.
+    from: Option<NaiveDate>,
+    /// The last date of the range, inclusive, only set along with `from`.
+    to: Option<NaiveDate>,
+    /// A part of the event labels, any case.
+    search: Option<String>,
+}
+
+impl EventFilter {
+    fn from_options(
+        options: &[ResolvedOption<'_>],
+        today: NaiveDate,
+        date_format: DateFormat,
+    ) -> Result<Self, Error> {
+        let days = get_integer_option(options, "days");
+        let month = get_integer_option(options, "month");
+        let from = get_string_option(options, "from")
+            .map(|from| parse_date(from, today, date_format))
+            .transpose()?;
+        // The next such date after the start of the range
+        let to = get_string_option(options, "to")
+            .map(|to| parse_date(to, from.unwrap_or(today), date_format))
+            .transpose()?;
+
+        let (from, to) = match (days, month, from, to) {
+            (None, None, None, None) => (None, None),
+            (Some(days), None, None, None) => {
+                let days = u64::try_from(days).unwrap_or_default().min(MAX_DAYS);
+                (Some(today), Some(today + Days::new(days)))
+            }
+            (None, Some(month), None, None) => {
+                // The month to come, this one included
+                let year = if month < today.month().into() {
+                    today.year() + 1
+                } else {
+                    today.year()
+                };
+                let first = u32::try_from(month)
+                    .ok()
+                    .and_then(|month| NaiveDate::from_ymd_opt(year, month, 1))
+                    .ok_or(Error::InvalidDateRange(format!("no month {month}")))?;
+                (Some(first), Some(first + Months::new(1) - Days::new(1)))
+            }
+            (None, None, from, to) => (Some(from.unwrap_or(today)), to),
+            _ => {
+                return Err(Error::InvalidDateRange(
+                    "use either days, month or from and to".into(),
+                ))
+            }
+        };
+        if let (Some(from), Some(to)) = (from, to) {
+            if to < from {
+                return Err(Error::InvalidDateRange(format!(
+                    "{} is before {}",
+                    date_format.format_date(to),
+                    date_format.format_date(from)
+                )));
+            }
+        }
+
+        let search = get_string_option(options, "search")
+            .map(str::trim)
+            .filter(|search| !search.is_empty())
+            .map(String::from);
+        Ok(Self { from, to, search })
+    }
+
+    fn page_custom_id(&self, page: usize) -> String {
+        let date = |date: Option<NaiveDate>| date.map(|date| date.to_string()).unwrap_or_default();
+        format!(
+            "{PAGE_BUTTON_PREFIX}{page}:{}:{}:{}",
+            date(self.from),
+            date(self.to),
+            self.search.as_deref().unwrap_or_default()
+        )
+    }
+
+    /// Reads the page and the filter of a page button, the search may contain colons.
+    fn from_page_custom_id(custom_id: &str) -> Option<(usize, Self)> {
+        let mut parts = custom_id.strip_prefix(PAGE_BUTTON_PREFIX)?.splitn(4, ':');
+        let page = parts.next()?.parse().ok()?;
+        let mut date = || match parts.next()? {
+            "" => Some(None),
+            date => date.parse().ok().map(Some),
+        };
+        let (from, to) = (date()?, date()?);
+        let search = parts.next()?;
+        let search = (!search.is_empty()).then(|| search.to_string());
+        Some((page, Self { from, to, search }))
+    }
+
+    fn matches_label(&self, event: &Event) -> bool {
+        let Some(search) = self.search.as_deref() else {
+            return true;
+        };
+        event
+            .summary
+            .as_deref()
+            .is_some_and(|label| label.to_lowercase().contains(&search.to_lowercase()))
+    }
+
+    fn describe(&self, date_format: DateFormat) -> String {
+        let date = |date| date_format.format_date(date);
+        let mut description = match (self.from, self.to) {
+            (Some(from), Some(to)) => format!("Events from {} to {}", date(from), date(to)),
+            (Some(from), None) => format!("Events since {}", date(from)),
+            _ => "Events".to_string(),
+        };
+        if let Some(search) = self.search.as_deref() {
+            description = format!("{description} matching \"{search}\"");
+        }
+        description
+    }
+}
+
+/// Responds with the first page, or the whole list as a file when downloading.
+#[instrument(skip(command))]
 pub async fn run(
     ctx: &Context,
+    command: &CommandInteraction,
     guild_id: &GuildId,
-    _options: &[ResolvedOption<'_>],
-) -> MessageResult {
+    options: &[ResolvedOption<'_>],
+) -> Result<(), Error> {
+    let download = get_bool_option(options, "download").unwrap_or_default();
+    let (time_zone, date_format) = {
+        let lock = ctx.data.read().await;
+        let pool = lock.get::<Pool>().ok_or(Error::NoPool)?;
+        (
+            get_guild_time_zone(pool, guild_id).await?,
+            get_guild_date_format(pool, guild_id).await?,
+        )
+    };
+    let today = Utc::now().with_timezone(&time_zone).date_naive();
+    let filter = EventFilter::from_options(options, today, date_format)?;
+
+    let message = match list_events(ctx, guild_id, &filter).await? {
+        None => CreateInteractionResponseMessage::new().content(NO_CALENDAR),
+        Some(list) if download => {
+            let description = filter.describe(list.date_format);
+            let mut content = format!("{description}: {}", list.lines.len());
+            if let Some(url) = &list.calendar_url {
+                content = format!("{content}\nCalendar: {url}");
+            }
+            let file = CreateAttachment::bytes(list.lines.join("\n"), "events.txt");
+            CreateInteractionResponseMessage::new()
+                .content(content)
+                .add_file(file)
+        }
+        Some(list) => page_message(&list, &filter, 0),
+    };
+    command
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(message.ephemeral(true)),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Shows the page of a Previous or Next button.
+#[instrument(skip(component))]
+pub async fn turn_page(ctx: &Context, component: &ComponentInteraction) -> Result<(), Error> {
+    let Some(guild_id) = component.guild_id else {
+        return Ok(());
+    };
+    let Some((page, filter)) = EventFilter::from_page_custom_id(&component.data.custom_id) else {
+        warn!(custom_id = component.data.custom_id, "Invalid page button");
+        return Ok(());
+    };
+    let message = match list_events(ctx, &guild_id, &filter).await? {
+        None => CreateInteractionResponseMessage::new()
+            .content(NO_CALENDAR)
+            .components(vec![]),
+        Some(list) => page_message(&list, &filter, page),
+    };
+    component
+        .create_response(&ctx.http, CreateInteractionResponse::UpdateMessage(message))
+        .await?;
+    Ok(())
+}
+
+/// The server events ordered by their next occurrence within the filter range, without a range
+/// the past ones go last.
+async fn list_events(
+    ctx: &Context,
+    guild_id: &GuildId,
+    filter: &EventFilter,
+) -> Result<Option<EventList>, Error> {
     info!("Fetching an event list for a guild");
     let lock = ctx.data.read().await;
-    let calendar_client = lock
-        .get::<CalendarClient>()
-        .ok_or(Error::NoCalendarClient)?;
-
-    let calendars = calendar_client.get_calendars_by_guild_id(guild_id).await?;
-
-    let calendar = match calendars {
-        Some(calendar) => calendar.id.expect("No calendar id"),
-        None => {
-            warn!("No calendar found for the server, escaping...");
-            return Ok(
-                "No calendar found for the server! Create a new one using `/create_calendar`"
-                    .into(),
-            );
+    let backend = lock.get::<Backend>().ok_or(Error::NoCalendarClient)?;
+    let pool = lock.get::<Pool>().ok_or(Error::NoPool)?;
+    let time_zone = get_guild_time_zone(pool, guild_id).await?;
+    let date_format = get_guild_date_format(pool, guild_id).await?;
+
+    let Some(calendar) = get_guild_calendar_id(pool, guild_id).await? else {
+        warn!("No calendar found for the server, escaping...");
+        return Ok(None);
+    };
+
+    let events = match filter.from {
+        // A spare day on both sides covers the timed events in any time zone
+        Some(from) => {
+            let from = EventStart::AllDay(from).date_time() - Duration::days(1);
+            let until = filter
+                .to
+                .map(|to| EventStart::AllDay(to).date_time() + Duration::days(2));
+            backend.list_events_between(&calendar, from, until).await?
         }
+        None => backend.list_events(&calendar).await?,
     };
+    info!(?events, "Returned event list");
 
-    let events = calendar_client.list_events(&calendar).await?;
+    let today = Utc::now().with_timezone(&time_zone).date_naive();
+    let mut events = events
+        .into_iter()
+        .filter(|event| filter.matches_label(event))
+        .filter_map(|event| {
+            let from = date_start(&event, filter.from.unwrap_or(today), &time_zone);
+            let until = filter
+                .to
+                .map(|to| date_start(&event, to + Days::new(1), &time_zone) - Duration::seconds(1));
+            let next = next_occurrence(&event, from, until).unwrap_or_else(|why| {
+                warn!(
+                    ?why,
+                    event_id = event.id,
+                    "Couldn't get the next occurrence"
+                );
+                None
+            });
+            if next.is_none() && filter.from.is_some() {
+                return None;
+            }
+            let start = next.or_else(|| EventStart::from_event(&event));
+            Some((
+                next.is_none(),
+                start.map(|start| start.date_time()),
+                start,
+                event,
+            ))
+        })
+        .collect::<Vec<_>>();
+    events.sort_by_key(|(is_over, date_time, ..)| (*is_over, *date_time));
 
-    info!(?events, "Returned event list");
+    let lines = events
+        .into_iter()
+        .map(|(is_over, _, start, event)| {
+            let date = match start {
+                Some(start) => format_occurrence(&event, start, &time_zone, date_format),
+                None => {
+                    warn!(event_id = event.id, "No start for the event");
+                    "No start".into()
+                }
+            };
+            let repeats = format_recurrence(&event, date_format);
+            let details = event_details(&event);
+            let label = event.summary.unwrap_or_else(|| {
+                warn!(event_id = event.id, "No label for the event");
+                "No label".into()
+            });
+            let line = match repeats {
+                _ if is_over => format!("{label}: {date} (over)"),
+                Some(repeats) => format!("{label}: {date} (repeats {repeats})"),
+                None => format!("{label}: {date}"),
+            };
+            match details {
+                Some(details) => format!("{line}\n    {details}"),
+                None => line,
+            }
+        })
+        .collect();
 
-    Ok(format!(
-        "Events:\n{}\nCalendar: {}",
-        events
-            .into_iter()
-            .map(|event| {
-                let label = event.summary.unwrap_or_else(|| {
-                    warn!(event_id = event.id, "No label for the event");
-                    "No label".into()
-                });
-                let date = match event.start {
-                    Some(start) => match start.date {
-                        Some(date) => date.to_string(),
-                        None => {
-                            warn!(event_id = event.id, "No date for the event");
-                            "No date".into()
-                        }
-                    }
-                    .to_string(),
-                    None => {
-                        warn!(event_id = event.id, "No start for the event");
-                        "No start".into()
-                    }
-                };
-                format!("{}: {}", label, date)
-            })
-            .collect::<Vec<_>>()
-            .join("\n"),
-        get_calendar_url(&calendar)
-    ))
+    Ok(Some(EventList {
+        lines,
+        calendar_url: backend.calendar_url(&calendar),
+        date_format,
+    }))
+}
+
+/// When the date starts for the event, all-day occurrences start at midnight UTC.
+fn date_start(event: &Event, date: NaiveDate, time_zone: &Tz) -> DateTime<Utc> {
+    let midnight_utc = EventStart::AllDay(date).date_time();
+    match EventStart::from_event(event) {
+        Some(EventStart::Timed(_)) => time_zone
+            .from_local_datetime(&date.and_time(NaiveTime::MIN))
+            .earliest()
+            .map_or(midnight_utc, |date_time| date_time.with_timezone(&Utc)),
+        _ => midnight_utc,
+    }
+}
+
+/// The description, the location and the link under the event line, the description cut to its
+/// first line and both cut short so a page stays short. The links are wrapped in `<>` to skip their previews.
+fn event_details(event: &Event) -> Option<String> {
+    let description = event.description.as_deref().and_then(|description| {
+        let line = description
+            .lines()
+            .find(|line| !line.trim().is_empty())?
+            .trim();
+        Some(shorten(line, MAX_DETAIL_LENGTH))
+    });
+    let location = event
+        .location
+        .as_deref()
+        .map(|location| format!("at {}", shorten(location, MAX_DETAIL_LENGTH)));
+    let url = event_url(event).map(|url| format!("<{url}>"));
+    let details = [description, location, url]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+    (!details.is_empty()).then(|| details.join(" · "))
+}
+
+/// Where the pages start, each one filled with up to `PAGE_SIZE` lines fitting the budget.
+fn page_starts(lines: &[String], budget: usize) -> Vec<usize> {
+    let mut starts = vec![0];
+    let mut length = 0;
+    for (index, line) in lines.iter().enumerate() {
+        // The line and its newline
+        let line_length = line.chars().count() + 1;
+        let page_lines = index - starts.last().copied().unwrap_or_default();
+        if page_lines > 0 && (page_lines == PAGE_SIZE || length + line_length > budget) {
+            starts.push(index);
+            length = 0;
+        }
+        length += line_length;
+    }
+    starts
+}
+
+fn page_message(
+    list: &EventList,
+    filter: &EventFilter,
+    page: usize,
+) -> CreateInteractionResponseMessage {
+    let mut url = String::new();
+    if let Some(calendar_url) = &list.calendar_url {
+        url = format!("\nCalendar: {calendar_url}");
+    }
+    if list.lines.is_empty() {
+        return CreateInteractionResponseMessage::new()
+            .content(format!(
+                "{}: none found{url}",
+                filter.describe(list.date_format)
+            ))
+            .components(vec![]);
+    }
+
+    let description = filter.describe(list.date_format);
+    let lines = list
+        .lines
+        .iter()
+        .map(|line| shorten(line, MAX_LINE_LENGTH))
+        .collect::<Vec<_>>();
+    let budget =
+        MAX_MESSAGE_LENGTH - PAGE_HEADER_LENGTH - description.chars().count() - url.chars().count();
+    let starts = page_starts(&lines, budget);
+    let pages = starts.len();
+    // The events may have changed since the button was shown
+    let page = page.min(pages - 1);
+    let end = starts.get(page + 1).copied().unwrap_or(lines.len());
+
+    let lines = lines[starts[page]..end].join("\n");
+    let content = format!("{description} (page {}/{pages}):\n{lines}{url}", page + 1);
+
+    let buttons = CreateActionRow::Buttons(vec![
+        CreateButton::new(filter.page_custom_id(page.saturating_sub(1)))
+            .label("Previous")
+            .style(ButtonStyle::Secondary)
+            .disabled(page == 0),
+        CreateButton::new(filter.page_custom_id(page + 1))
+            .label("Next")
+            .style(ButtonStyle::Secondary)
+            .disabled(page + 1 >= pages),
+    ]);
+    CreateInteractionResponseMessage::new()
+        .content(content)
+        .components(vec![buttons])
 }
 
 pub fn register() -> CreateCommand {
-    CreateCommand::new("list_events").description("List all the events on the server")
+    CreateCommand::new("list_events")
+        .description("List the events on the server, all of them unless narrowed down")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "days",
+                "Only the events within this many days from today",
+            )
+            .min_int_value(0)
+            .max_int_value(MAX_DAYS)
+            .required(false),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "month",
+                "Only the events of the month to come, 1 to 12",
+            )
+            .min_int_value(1)
+            .max_int_value(12)
+            .required(false),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "from",
+                "Only the events since the date, e.g. 1 dec or next monday, today by default",
+            )
+            .required(false),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "to",
+                "Only the events until the date, e.g. 31 dec or in 2 weeks",
+            )
+            .required(false),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "search",
+                "Only the events with labels containing the text",
+            )
+            .max_length(MAX_SEARCH_LENGTH)
+            .required(false),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Boolean,
+                "download",
+                "Attach the whole list as a file instead of showing pages",
+            )
+            .required(false),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{page_starts, PAGE_SIZE};
+
+    #[test]
+    fn fills_the_pages_up_to_the_page_size() {
+        let lines = vec!["event".to_string(); 25];
+        assert_eq!(page_starts(&lines, 1900), vec![0, PAGE_SIZE, 2 * PAGE_SIZE]);
+    }
+
+    #[test]
+    fn starts_a_page_when_the_budget_runs_out() {
+        let lines = vec!["a".repeat(299); 10];
+        assert_eq!(page_starts(&lines, 1900), vec![0, 6]);
+    }
+
+    #[test]
+    fn keeps_a_line_longer_than_the_budget_on_its_own_page() {
+        let lines = vec!["a".repeat(10), "b".repeat(100), "c".repeat(10)];
+        assert_eq!(page_starts(&lines, 50), vec![0, 1, 2]);
+    }
 }
@@ -1,10 +1,21 @@
+use serenity::all::Context;
 use serenity::builder::CreateCommand;
 use serenity::model::application::ResolvedOption;
 
-pub fn run(_options: &[ResolvedOption<'_>]) -> String {
-    "Hey, I'm alive!".to_string()
+use crate::supervisor::Tasks;
+
+/// Answers with the health of the background tasks as well.
+pub async fn run(ctx: &Context, _options: &[ResolvedOption<'_>]) -> String {
+    let mut content = "Hey, I'm alive!".to_string();
+    let supervisor = ctx.data.read().await.get::<Tasks>().cloned();
+    if let Some(supervisor) = supervisor {
+        for (name, status) in supervisor.health().await {
+            content.push_str(&format!("\n{name}: {status}"));
+        }
+    }
+    content
 }
 
 pub fn register() -> CreateCommand {
-    CreateCommand::new("ping").description("A ping command")
+    CreateCommand::new("ping").description("Check the bot and its background tasks")
 }
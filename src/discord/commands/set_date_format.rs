@@ -0,0 +1,52 @@
+use chrono::Utc;
+use serenity::all::{
+    CommandOptionType, Context, CreateCommand, CreateCommandOption, GuildId, Permissions,
+    ResolvedOption,
+};
+use tracing::{info, instrument};
+
+use crate::dates::DateFormat;
+use crate::discord::{get_guild_time_zone, set_guild_date_format};
+use crate::{Error, Pool};
+
+use super::{get_string_option, MessageResult};
+
+#[instrument]
+pub async fn run(
+    ctx: &Context,
+    guild_id: &GuildId,
+    options: &[ResolvedOption<'_>],
+) -> MessageResult {
+    let Some(format) = get_string_option(options, "format") else {
+        return Err(Error::MissingParameter("format".into()));
+    };
+    let format = DateFormat::parse(format)?;
+
+    info!("Setting the server date format");
+    let lock = ctx.data.read().await;
+    let pool = lock.get::<Pool>().ok_or(Error::NoPool)?;
+    set_guild_date_format(pool, guild_id, format).await?;
+    let time_zone = get_guild_time_zone(pool, guild_id).await?;
+    let today = Utc::now().with_timezone(&time_zone).date_naive();
+    Ok(format!(
+        "The dates are written like {} now!",
+        format.format_date(today)
+    ))
+}
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("set_date_format")
+        .description("Set how the dates are typed in the commands and shown by the bot")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "format",
+                "The order of the day, the month and the year",
+            )
+            .add_string_choice("ISO: 2026-12-25, 12-25", DateFormat::Iso.as_str())
+            .add_string_choice("US: 12/25/2026, 12/25", DateFormat::Us.as_str())
+            .add_string_choice("EU: 25.12.2026, 25.12", DateFormat::Eu.as_str())
+            .required(true),
+        )
+        .default_member_permissions(Permissions::ADMINISTRATOR)
+}
@@ -0,0 +1,48 @@
+use serenity::all::{
+    CommandOptionType, Context, CreateCommand, CreateCommandOption, GuildId, Permissions,
+    ResolvedOption,
+};
+use tracing::{info, instrument};
+
+use crate::discord::set_guild_notification_style;
+use crate::notifier::NotificationStyle;
+use crate::{Error, Pool};
+
+use super::{get_string_option, MessageResult};
+
+#[instrument]
+pub async fn run(
+    ctx: &Context,
+    guild_id: &GuildId,
+    options: &[ResolvedOption<'_>],
+) -> MessageResult {
+    let Some(style) = get_string_option(options, "style") else {
+        return Err(Error::MissingParameter("style".into()));
+    };
+    let style = NotificationStyle::parse(style)?;
+
+    info!("Setting the server notification style");
+    let lock = ctx.data.read().await;
+    let pool = lock.get::<Pool>().ok_or(Error::NoPool)?;
+    set_guild_notification_style(pool, guild_id, style).await?;
+    Ok(match style {
+        NotificationStyle::Embed => "The notifications are posted as embeds!".into(),
+        NotificationStyle::Text => "The notifications are posted as plain text!".into(),
+    })
+}
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("set_notification_style")
+        .description("Set how the event notifications are posted")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "style",
+                "Rich embeds or plain text messages",
+            )
+            .add_string_choice("Embed", NotificationStyle::Embed.as_str())
+            .add_string_choice("Text", NotificationStyle::Text.as_str())
+            .required(true),
+        )
+        .default_member_permissions(Permissions::ADMINISTRATOR)
+}
@@ -0,0 +1,46 @@
+use serenity::all::{
+    CommandOptionType, Context, CreateCommand, CreateCommandOption, GuildId, Permissions,
+    ResolvedOption,
+};
+use tracing::{info, instrument};
+
+use crate::discord::set_guild_reminder_days;
+use crate::notifier::{format_reminder_days, parse_reminder_days};
+use crate::{Error, Pool};
+
+use super::{get_string_option, MessageResult};
+
+#[instrument]
+pub async fn run(
+    ctx: &Context,
+    guild_id: &GuildId,
+    options: &[ResolvedOption<'_>],
+) -> MessageResult {
+    let Some(days) = get_string_option(options, "days") else {
+        return Err(Error::MissingParameter("days".into()));
+    };
+    let reminder_days = parse_reminder_days(days)?;
+
+    info!("Setting the server reminders");
+    let lock = ctx.data.read().await;
+    let pool = lock.get::<Pool>().ok_or(Error::NoPool)?;
+    set_guild_reminder_days(pool, guild_id, &reminder_days).await?;
+    Ok(format!(
+        "The events will be reminded about {} days before!",
+        format_reminder_days(&reminder_days)
+    ))
+}
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("set_reminders")
+        .description("Set how many days before the events the notifications are sent")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "days",
+                "Comma separated days before the event, 0 is the day itself, e.g. 7,1,0",
+            )
+            .required(true),
+        )
+        .default_member_permissions(Permissions::ADMINISTRATOR)
+}
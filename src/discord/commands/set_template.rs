@@ -0,0 +1,178 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use serenity::all::{
+    ButtonStyle, CommandInteraction, CommandOptionType, Context, CreateActionRow, CreateButton,
+    CreateCommand, CreateCommandOption, CreateInteractionResponse,
+    CreateInteractionResponseMessage, EditInteractionResponse, GuildId, Permissions,
+    ResolvedOption,
+};
+use tracing::{info, instrument};
+
+use crate::{
+    calendar::{get_guild_calendar_id, Backend, EventStart},
+    discord::{get_guild_date_format, get_guild_time_zone},
+    notifier::{
+        delete_notification_template, format_placeholders, render_template,
+        set_notification_template, TemplateValues, MAX_TEMPLATE_LENGTH,
+    },
+    Error, Pool,
+};
+
+use super::get_string_option;
+
+/// How long the preview waits for the template to be saved.
+const CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(60);
+const SAVE_BUTTON: &str = "set_template:save";
+const CANCEL_BUTTON: &str = "set_template:cancel";
+
+/// Previews the template and saves it once confirmed, so the command responds on its own.
+#[instrument(skip(command))]
+pub async fn run(
+    ctx: &Context,
+    command: &CommandInteraction,
+    guild_id: &GuildId,
+    options: &[ResolvedOption<'_>],
+) -> Result<(), Error> {
+    let Some(kind) = get_string_option(options, "kind") else {
+        return Err(Error::MissingParameter("kind".into()));
+    };
+    let category = get_string_option(options, "category")
+        .map(str::trim)
+        .unwrap_or_default();
+    let pool = {
+        let lock = ctx.data.read().await;
+        lock.get::<Pool>().ok_or(Error::NoPool)?.clone()
+    };
+
+    let Some(template) = get_string_option(options, "template") else {
+        info!("Resetting the notification template");
+        delete_notification_template(&pool, guild_id, kind, category).await?;
+        respond(ctx, command, "The default notification text is used again!").await?;
+        return Ok(());
+    };
+    let preview = render_template(template, &preview_values(ctx, guild_id, kind).await?)?;
+
+    let buttons = CreateActionRow::Buttons(vec![
+        CreateButton::new(SAVE_BUTTON)
+            .label("Save")
+            .style(ButtonStyle::Success),
+        CreateButton::new(CANCEL_BUTTON)
+            .label("Cancel")
+            .style(ButtonStyle::Secondary),
+    ]);
+    let message = CreateInteractionResponseMessage::new()
+        .content(format!(
+            "Preview:\n{preview}\n\nPlaceholders: {}",
+            format_placeholders()
+        ))
+        .components(vec![buttons])
+        .ephemeral(true);
+    command
+        .create_response(&ctx.http, CreateInteractionResponse::Message(message))
+        .await?;
+
+    let response = command.get_response(&ctx.http).await?;
+    let Some(interaction) = response
+        .await_component_interaction(&ctx.shard)
+        .author_id(command.user.id)
+        .timeout(CONFIRMATION_TIMEOUT)
+        .await
+    else {
+        let message = EditInteractionResponse::new()
+            .content("The template wasn't saved in time, try again!")
+            .components(vec![]);
+        command.edit_response(&ctx.http, message).await?;
+        return Ok(());
+    };
+
+    let content = if interaction.data.custom_id == SAVE_BUTTON {
+        info!("Saving the notification template");
+        match set_notification_template(&pool, guild_id, kind, category, template).await {
+            Ok(()) => format!("The template is saved!\n{preview}"),
+            Err(why) => format!("Error: {why}"),
+        }
+    } else {
+        "The template wasn't saved".into()
+    };
+    let message = CreateInteractionResponseMessage::new()
+        .content(content)
+        .components(vec![]);
+    interaction
+        .create_response(&ctx.http, CreateInteractionResponse::UpdateMessage(message))
+        .await?;
+    Ok(())
+}
+
+/// Example values, a reminder three days ahead of the first anniversary of a three-day event.
+async fn preview_values(
+    ctx: &Context,
+    guild_id: &GuildId,
+    kind: &str,
+) -> Result<TemplateValues<'static>, Error> {
+    let lock = ctx.data.read().await;
+    let backend = lock.get::<Backend>().ok_or(Error::NoCalendarClient)?;
+    let pool = lock.get::<Pool>().ok_or(Error::NoPool)?;
+    let time_zone = get_guild_time_zone(pool, guild_id).await?;
+    let date_format = get_guild_date_format(pool, guild_id).await?;
+    let calendar_url = get_guild_calendar_id(pool, guild_id)
+        .await?
+        .and_then(|calendar_id| backend.calendar_url(&calendar_id));
+
+    let days_until = if kind == "reminder" { 3 } else { 0 };
+    let date = Utc::now().with_timezone(&time_zone).date_naive() + chrono::Days::new(days_until);
+    Ok(TemplateValues {
+        label: "Example event",
+        date: EventStart::AllDay(date).format_in(&time_zone, date_format),
+        days_until: days_until as i64,
+        years: 1,
+        calendar_url,
+        day: if kind == "ongoing" { 2 } else { 1 },
+        days: 3,
+    })
+}
+
+async fn respond(ctx: &Context, command: &CommandInteraction, content: &str) -> Result<(), Error> {
+    let message = CreateInteractionResponseMessage::new()
+        .content(content)
+        .ephemeral(true);
+    command
+        .create_response(&ctx.http, CreateInteractionResponse::Message(message))
+        .await?;
+    Ok(())
+}
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("set_template")
+        .description("Set the text of the event notifications, previewed before saving")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "kind",
+                "Which notifications use the template",
+            )
+            .add_string_choice("On the day of an all-day event", "today")
+            .add_string_choice("At the start of a timed event", "start")
+            .add_string_choice("Reminders before the event", "reminder")
+            .add_string_choice("Every later day of a multi-day event", "ongoing")
+            .required(true),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "template",
+                "The text with {placeholders}, empty for the default text",
+            )
+            .max_length(MAX_TEMPLATE_LENGTH)
+            .required(false),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "category",
+                "Use the template only for the events of the category",
+            )
+            .required(false),
+        )
+        .default_member_permissions(Permissions::ADMINISTRATOR)
+}
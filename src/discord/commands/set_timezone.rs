@@ -0,0 +1,45 @@
+use chrono_tz::Tz;
+use serenity::all::{
+    CommandOptionType, Context, CreateCommand, CreateCommandOption, GuildId, Permissions,
+    ResolvedOption,
+};
+use tracing::{info, instrument};
+
+use crate::discord::set_guild_time_zone;
+use crate::{Error, Pool};
+
+use super::{get_string_option, MessageResult};
+
+#[instrument]
+pub async fn run(
+    ctx: &Context,
+    guild_id: &GuildId,
+    options: &[ResolvedOption<'_>],
+) -> MessageResult {
+    let Some(time_zone) = get_string_option(options, "timezone") else {
+        return Err(Error::MissingParameter("timezone".into()));
+    };
+    let time_zone: Tz = time_zone
+        .parse()
+        .map_err(|_| Error::InvalidTimeZone(time_zone.into()))?;
+
+    info!("Setting the server time zone");
+    let lock = ctx.data.read().await;
+    let pool = lock.get::<Pool>().ok_or(Error::NoPool)?;
+    set_guild_time_zone(pool, guild_id, &time_zone).await?;
+    Ok(format!("The server time zone is set to {time_zone}!"))
+}
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("set_timezone")
+        .description("Set the time zone the server dates and times are in")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "timezone",
+                "The IANA time zone name, e.g. Europe/Berlin",
+            )
+            .required(true),
+        )
+        .default_member_permissions(Permissions::ADMINISTRATOR)
+}
@@ -1,15 +1,25 @@
 use crate::discord::commands;
-use crate::{calendar::Client as CalendarClient, Error};
+use crate::{
+    calendar::{get_guild_calendar_id, set_guild_calendar, Backend},
+    shutdown::Shutdown,
+    Error, Pool,
+};
 use serenity::{
     all::{
-        Context, CreateInteractionResponse, CreateInteractionResponseMessage, EventHandler, Guild,
-        GuildId, Http, Interaction, Ready,
+        AutocompleteChoice, AutocompleteOption, CommandInteraction, ComponentInteraction, Context,
+        CreateAutocompleteResponse, CreateInteractionResponse, CreateInteractionResponseMessage,
+        EventHandler, Guild, GuildId, Http, Interaction, Ready,
     },
     async_trait,
 };
 use tracing::{error, info, instrument, warn};
 
-use super::commands::MessageResult;
+use super::{commands::MessageResult, LabelCache};
+
+/// Discord shows at most 25 suggestions.
+const MAX_AUTOCOMPLETE_CHOICES: usize = 25;
+const MAX_CHOICE_LENGTH: usize = 100;
+const RESTARTING: &str = "The bot is restarting, try again in a minute!";
 
 #[derive(Debug)]
 pub struct Handler;
@@ -23,7 +33,7 @@ impl EventHandler for Handler {
             Some(is_new) => {
                 if is_new {
                     info!("Added to {} server", guild.name);
-                    if let Err(why) = create_calendar(&ctx, guild.name).await {
+                    if let Err(why) = create_calendar(&ctx, &guild.id).await {
                         error!(?why, "Failed to create calendar");
                     };
                     init_commands(&ctx.http, &guild.id).await;
@@ -46,6 +56,27 @@ impl EventHandler for Handler {
 
     #[instrument]
     async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        // The shutdown waits for the interactions being handled
+        let shutdown = ctx.data.read().await.get::<Shutdown>().cloned();
+        let _running = shutdown.as_ref().map(|shutdown| shutdown.tracker.token());
+        let shutting_down = shutdown.is_some_and(|shutdown| shutdown.is_requested());
+
+        match &interaction {
+            Interaction::Autocomplete(_) if shutting_down => {
+                info!("Shutting down, skipping the suggestions");
+                return;
+            }
+            Interaction::Autocomplete(autocomplete) => {
+                suggest_labels(&ctx, autocomplete).await;
+                return;
+            }
+            Interaction::Component(component) => {
+                handle_component(&ctx, component, shutting_down).await;
+                return;
+            }
+            _ => (),
+        }
+
         if let Interaction::Command(command) = interaction {
             info!(?command, "Received command interaction");
 
@@ -57,7 +88,11 @@ impl EventHandler for Handler {
             let options = command.data.options();
 
             let content = match command.data.name.as_ref() {
-                "ping" => Some(commands::ping::run(&options)),
+                _ if shutting_down => {
+                    info!("Shutting down, rejecting the command");
+                    Some(RESTARTING.to_string())
+                }
+                "ping" => Some(commands::ping::run(&ctx, &options).await),
                 "create_calendar" => Some(result_to_message(
                     commands::create_calendar::run(&ctx, guild_id, &options).await,
                 )),
@@ -67,15 +102,41 @@ impl EventHandler for Handler {
                 "set_event_channel" => Some(result_to_message(
                     commands::set_event_channel::run(&ctx, guild_id, channel_id, &options).await,
                 )),
-                "list_events" => Some(result_to_message(
-                    commands::list_events::run(&ctx, &guild_id, &options).await,
-                )),
+                // Responds on its own to attach the page buttons or the list file
+                "list_events" => {
+                    match commands::list_events::run(&ctx, &command, &guild_id, &options).await {
+                        Ok(()) => None,
+                        Err(why) => Some(result_to_message(Err(why))),
+                    }
+                }
                 "create_event" => Some(result_to_message(
                     commands::create_event::run(&ctx, &guild_id, &options).await,
                 )),
+                "edit_event" => Some(result_to_message(
+                    commands::edit_event::run(&ctx, &guild_id, &options).await,
+                )),
                 "delete_event" => Some(result_to_message(
                     commands::delete_event::run(&ctx, &guild_id, &options).await,
                 )),
+                "set_reminders" => Some(result_to_message(
+                    commands::set_reminders::run(&ctx, &guild_id, &options).await,
+                )),
+                // Responds on its own to preview the template before saving it
+                "set_template" => {
+                    match commands::set_template::run(&ctx, &command, &guild_id, &options).await {
+                        Ok(()) => None,
+                        Err(why) => Some(result_to_message(Err(why))),
+                    }
+                }
+                "set_notification_style" => Some(result_to_message(
+                    commands::set_notification_style::run(&ctx, &guild_id, &options).await,
+                )),
+                "set_timezone" => Some(result_to_message(
+                    commands::set_timezone::run(&ctx, &guild_id, &options).await,
+                )),
+                "set_date_format" => Some(result_to_message(
+                    commands::set_date_format::run(&ctx, &guild_id, &options).await,
+                )),
                 command => {
                     error!("An unimplemented command met: {command}");
                     Some("not implemented".to_string())
@@ -95,16 +156,99 @@ impl EventHandler for Handler {
     }
 }
 
+/// Handles the buttons of the command responses, the ones awaited by a command are left to it.
+#[instrument(skip(ctx, component))]
+async fn handle_component(ctx: &Context, component: &ComponentInteraction, shutting_down: bool) {
+    let custom_id = component.data.custom_id.as_str();
+    if !custom_id.starts_with(commands::list_events::PAGE_BUTTON_PREFIX) {
+        return;
+    }
+    let content = if shutting_down {
+        info!("Shutting down, rejecting the component interaction");
+        RESTARTING.to_string()
+    } else {
+        match commands::list_events::turn_page(ctx, component).await {
+            Ok(()) => return,
+            Err(why) => {
+                error!(?why, "Failed to handle the component interaction");
+                format!("Error: {why}")
+            }
+        }
+    };
+    let data = CreateInteractionResponseMessage::new()
+        .content(content)
+        .ephemeral(true);
+    let builder = CreateInteractionResponse::Message(data);
+    if let Err(why) = component.create_response(&ctx.http, builder).await {
+        error!("Cannot respond to component interaction: {why}");
+    }
+}
+
+/// Suggests the server event labels matching what's typed in a `label` option.
+#[instrument(skip(ctx, autocomplete))]
+async fn suggest_labels(ctx: &Context, autocomplete: &CommandInteraction) {
+    let Some(guild_id) = autocomplete.guild_id else {
+        return;
+    };
+    let Some(AutocompleteOption {
+        name: "label",
+        value,
+        ..
+    }) = autocomplete.data.autocomplete()
+    else {
+        return;
+    };
+
+    let labels = {
+        let lock = ctx.data.read().await;
+        let (Some(backend), Some(pool), Some(cache)) = (
+            lock.get::<Backend>(),
+            lock.get::<Pool>(),
+            lock.get::<LabelCache>(),
+        ) else {
+            error!("No calendar backend, pool or label cache in data");
+            return;
+        };
+        match cache.labels(&guild_id, backend.as_ref(), pool).await {
+            Ok(labels) => labels,
+            Err(why) => {
+                error!(?why, "Couldn't list the event labels");
+                return;
+            }
+        }
+    };
+
+    let value = value.to_lowercase();
+    let choices = labels
+        .into_iter()
+        // Longer choices make Discord reject the whole response
+        .filter(|label| label.chars().count() <= MAX_CHOICE_LENGTH)
+        .filter(|label| label.to_lowercase().contains(&value))
+        .take(MAX_AUTOCOMPLETE_CHOICES)
+        .map(|label| AutocompleteChoice::new(label.clone(), label))
+        .collect();
+    let builder = CreateInteractionResponse::Autocomplete(
+        CreateAutocompleteResponse::new().set_choices(choices),
+    );
+    if let Err(why) = autocomplete.create_response(&ctx.http, builder).await {
+        error!("Cannot respond to autocomplete: {why}");
+    }
+}
+
 #[instrument]
-async fn create_calendar(ctx: &Context, name: String) -> Result<(), Error> {
+async fn create_calendar(ctx: &Context, guild_id: &GuildId) -> Result<(), Error> {
+    let lock = ctx.data.read().await;
+    let backend = lock.get::<Backend>().ok_or(Error::NoCalendarClient)?;
+    let pool = lock.get::<Pool>().ok_or(Error::NoPool)?;
+    if get_guild_calendar_id(pool, guild_id).await?.is_some() {
+        info!("The server already has a calendar");
+        return Ok(());
+    }
+
     info!("Pushing a calendar to queue");
-    ctx.data
-        .read()
-        .await
-        .get::<CalendarClient>()
-        .ok_or(Error::NoCalendarClient)?
-        .create_calendar(&name)
-        .await?;
+    let calendar = backend.create_calendar(&guild_id.to_string()).await?;
+    let calendar_id = calendar.id.ok_or(Error::MissingCalendarId)?;
+    set_guild_calendar(pool, guild_id, &calendar_id).await?;
     Ok(())
 }
 
@@ -120,6 +264,12 @@ async fn init_commands(http: &Http, guild: &GuildId) {
                 commands::list_events::register(),
                 commands::create_event::register(),
                 commands::delete_event::register(),
+                commands::edit_event::register(),
+                commands::set_reminders::register(),
+                commands::set_timezone::register(),
+                commands::set_date_format::register(),
+                commands::set_notification_style::register(),
+                commands::set_template::register(),
             ],
         )
         .await
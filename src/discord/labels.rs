@@ -0,0 +1,64 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use serenity::{
+    all::GuildId,
+    prelude::{RwLock, TypeMapKey},
+};
+use sqlx::PgPool;
+use tokio::time::Instant;
+
+use crate::{
+    calendar::{get_guild_calendar_id, CalendarBackend},
+    Error,
+};
+
+/// Autocompletion asks on every keystroke, so the labels are listed once in a while.
+const LABELS_TTL: Duration = Duration::from_secs(60);
+
+/// When the labels were listed and the labels.
+type CachedLabels = (Instant, Vec<String>);
+
+/// The event labels of the servers, refreshed when stale or after the events change.
+#[derive(Clone, Default)]
+pub struct LabelCache(Arc<RwLock<HashMap<GuildId, CachedLabels>>>);
+
+impl TypeMapKey for LabelCache {
+    type Value = LabelCache;
+}
+
+impl LabelCache {
+    /// The sorted unique labels of the server events.
+    pub async fn labels(
+        &self,
+        guild_id: &GuildId,
+        backend: &dyn CalendarBackend,
+        pool: &PgPool,
+    ) -> Result<Vec<String>, Error> {
+        if let Some((fetched_at, labels)) = self.0.read().await.get(guild_id) {
+            if fetched_at.elapsed() < LABELS_TTL {
+                return Ok(labels.clone());
+            }
+        }
+
+        let Some(calendar_id) = get_guild_calendar_id(pool, guild_id).await? else {
+            return Ok(vec![]);
+        };
+        let mut labels = backend
+            .list_events(&calendar_id)
+            .await?
+            .into_iter()
+            .filter_map(|event| event.summary)
+            .collect::<Vec<_>>();
+        labels.sort_unstable();
+        labels.dedup();
+        self.0
+            .write()
+            .await
+            .insert(*guild_id, (Instant::now(), labels.clone()));
+        Ok(labels)
+    }
+
+    pub async fn invalidate(&self, guild_id: &GuildId) {
+        self.0.write().await.remove(guild_id);
+    }
+}
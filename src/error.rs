@@ -3,26 +3,98 @@ use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum Error {
-    #[error("The calendar summary {0} is not a discord server id")]
-    CalendarSummaryNotDiscordServerId(String),
-
     #[error("The server {0} has no event channel")]
     DiscordSeverHasNoEventChannel(GuildId),
 
     #[error("No pool in data")]
     NoPool,
 
-    #[error("No calendar client in data")]
+    #[error("No calendar backend in data")]
     NoCalendarClient,
 
+    #[error("The Google service account key is missing")]
+    MissingGoogleSecret,
+
+    #[error("The CalDAV backend isn't configured")]
+    MissingCalDavConfig,
+
+    #[error("Invalid iCalendar data: {0}")]
+    InvalidICalendar(String),
+
+    #[error("Unknown time zone {0}, use an IANA name like Europe/Berlin")]
+    InvalidTimeZone(String),
+
+    #[error("The time {0} doesn't exist in the server time zone")]
+    NonexistentLocalTime(chrono::NaiveDateTime),
+
+    #[error("Invalid reminders {0}, use comma separated days before the event, e.g. 7,1,0")]
+    InvalidReminderDays(String),
+
+    #[error("Unknown notification style {0}, use embed or text")]
+    InvalidNotificationStyle(String),
+
+    #[error("Invalid template: {0}")]
+    InvalidTemplate(String),
+
+    #[error("Invalid image url {0}")]
+    InvalidImageUrl(String),
+
+    #[error("Invalid url {0}, use an http or https link")]
+    InvalidUrl(String),
+
+    #[error("Invalid date {0}, use the server date format, 25 dec, next friday or in 3 days")]
+    InvalidDate(String),
+
+    #[error("Unknown date format {0}, use iso, us or eu")]
+    InvalidDateFormat(String),
+
+    #[error("Invalid event end: {0}")]
+    InvalidEventEnd(String),
+
+    #[error("Invalid date range: {0}")]
+    InvalidDateRange(String),
+
+    #[error("Invalid repetition: {0}")]
+    InvalidRecurrence(String),
+
     #[error("Required parameter {0} is missing")]
     MissingParameter(String),
 
+    #[error("The event {0} doesn't exist")]
+    EventNotFound(String),
+
+    #[error("The calendar has no id")]
+    MissingCalendarId,
+
+    #[error("The event has no id")]
+    MissingEventId,
+
+    #[error("The Google API didn't respond in time")]
+    GoogleTimeout,
+
+    #[error("The calendar response has no {0}")]
+    MissingResponseField(&'static str),
+
     #[error(transparent)]
     DbError(#[from] sqlx::Error),
 
     #[error(transparent)]
-    GoogleError(#[from] google_calendar3::Error),
+    GoogleError(Box<google_calendar3::Error>),
+
+    #[error(transparent)]
+    CalDavError(#[from] reqwest::Error),
+
+    #[error(transparent)]
+    XmlError(#[from] roxmltree::Error),
+
+    #[error(transparent)]
+    UrlError(#[from] url::ParseError),
+
+    #[error(transparent)]
+    RecurrenceError(#[from] rrule::RRuleError),
+
+    #[error(transparent)]
+    JsonError(#[from] serde_json::Error),
 
     #[error(transparent)]
     IoError(#[from] std::io::Error),
@@ -31,8 +103,20 @@ pub enum Error {
     ConfigError(#[from] config::ConfigError),
 
     #[error(transparent)]
-    SerenityError(#[from] serenity::Error),
+    SerenityError(Box<serenity::Error>),
 
     #[error(transparent)]
     ChronoParseError(#[from] chrono::ParseError),
 }
+
+impl From<google_calendar3::Error> for Error {
+    fn from(value: google_calendar3::Error) -> Self {
+        Self::GoogleError(Box::new(value))
+    }
+}
+
+impl From<serenity::Error> for Error {
+    fn from(value: serenity::Error) -> Self {
+        Self::SerenityError(Box::new(value))
+    }
+}
@@ -1,34 +1,41 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
+use calendar::Backend;
+use calendar::CalDavBackend;
+use calendar::CalendarBackend;
 use calendar::Client as CalendarClient;
-use chrono::Utc;
+use calendar::PostgresBackend;
 use config::AppConfig;
+use config::CalendarBackendKind;
 use discord::Client as DiscordClient;
 use discord::Handler;
-use futures::StreamExt;
-use google_calendar3::api::CalendarListEntry;
-use google_calendar3::api::Event;
-use google_calendar3::api::EventDateTime;
+use discord::LabelCache;
+use notifier::Notifier;
 use secrecy::ExposeSecret;
-use serenity::all::CreateMessage;
-use serenity::all::GuildId;
-use serenity::all::Http;
 use serenity::prelude::*;
 use serenity::Client as SerenityClient;
+use shutdown::Shutdown;
 use sqlx::PgPool;
-use tokio::task::JoinHandle;
-use tracing::error;
-use tracing::instrument;
-use tracing::warn;
+use supervisor::{Supervisor, Tasks};
+use tracing::{error, info, warn};
 
 pub mod config;
 
 mod calendar;
+mod dates;
 mod discord;
+mod notifier;
+mod shutdown;
+mod supervisor;
+mod text;
 
 mod error;
 pub use error::*;
 
+/// How long the notifications being sent and the commands being handled may take to finish on
+/// shutdown.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(20);
+
 pub struct Pool;
 
 impl TypeMapKey for Pool {
@@ -45,18 +52,48 @@ impl Client {
             | GatewayIntents::MESSAGE_CONTENT
             | GatewayIntents::GUILDS;
 
-        let calendar_client =
-            CalendarClient::with_sa_key(config.google_secret.expose_secret()).await?;
+        let calendar_client: Arc<dyn CalendarBackend> = match config.calendar.backend {
+            CalendarBackendKind::Google => {
+                let google_secret = config
+                    .google_secret
+                    .as_ref()
+                    .ok_or(Error::MissingGoogleSecret)?;
+                Arc::new(
+                    CalendarClient::with_sa_key(
+                        google_secret.expose_secret(),
+                        pool.clone(),
+                        &config.calendar.google,
+                    )
+                    .await?,
+                )
+            }
+            CalendarBackendKind::Postgres => Arc::new(PostgresBackend::new(pool.clone())),
+            CalendarBackendKind::CalDav => {
+                let caldav_config = config
+                    .calendar
+                    .caldav
+                    .as_ref()
+                    .ok_or(Error::MissingCalDavConfig)?;
+                Arc::new(CalDavBackend::new(caldav_config)?)
+            }
+        };
+
+        calendar::adopt_guild_calendars(&pool, calendar_client.as_ref()).await?;
 
         let serenity_client =
             SerenityClient::builder(config.discord_access_token.expose_secret(), intents)
                 .event_handler(Handler)
                 .await?;
         let serenity_data = serenity_client.data.clone();
+        let shutdown = Shutdown::default();
+        let supervisor = Supervisor::new(shutdown.token.clone());
         {
             let mut data = serenity_data.write().await;
-            data.insert::<CalendarClient>(calendar_client.clone());
-            data.insert::<Pool>(pool);
+            data.insert::<Backend>(calendar_client.clone());
+            data.insert::<Pool>(pool.clone());
+            data.insert::<Tasks>(supervisor.clone());
+            data.insert::<Shutdown>(shutdown.clone());
+            data.insert::<LabelCache>(LabelCache::default());
         }
 
         let discalen_client = Self {
@@ -65,94 +102,54 @@ impl Client {
         let discord_client = discalen_client.discord_client;
         let sender_http = discord_client.serenity_client.http.clone();
         let discord_data = discord_client.serenity_client.data.clone();
+        let shard_manager = discord_client.serenity_client.shard_manager.clone();
 
-        let discord_task = tokio::spawn(async move {
+        let mut discord_task = tokio::spawn(async move {
             let mut serenity_client = discord_client.serenity_client;
             if let Err(why) = serenity_client.start_autosharded().await {
                 error!("Client error: {why:?}");
             }
         });
 
-        let calendar_task: JoinHandle<Result<(), Error>> = tokio::spawn(async move {
-            let calendar_client = calendar_client;
-            loop {
-                tokio::time::sleep(config.notification_period).await;
-
-                let calendars = calendar_client.list_calendars().await?;
+        let notifier = Arc::new(Notifier {
+            backend: calendar_client,
+            pool: pool.clone(),
+            data: discord_data,
+            http: sender_http,
+            period: config.notification_period,
+            shutdown: shutdown.clone(),
+        });
+        // A failing calendar only restarts the notifier, the bot stays online
+        supervisor.spawn("notifier", move || {
+            let notifier = notifier.clone();
+            async move { notifier.run().await }
+        });
 
-                let mut calendars_handles = vec![];
-                for calendar in &calendars {
-                    let handle =
-                        calendar_client.list_events(calendar.id.as_ref().expect("No id specified"));
-                    calendars_handles.push((calendar, handle));
+        tokio::select! {
+            result = &mut discord_task => {
+                if let Err(why) = result {
+                    error!(?why, "The Discord client has panicked");
                 }
-
-                let mut stream = futures::stream::iter(calendars_handles);
-                while let Some(handle) = stream.next().await {
-                    let (calendar, events) = handle;
-                    let events = events.await?;
-
-                    let mut sending_tasks = vec![];
-                    for event in events {
-                        let date = match event.start {
-                            Some(EventDateTime {
-                                date: Some(date), ..
-                            }) => date,
-                            _ => {
-                                warn!(?event, "The event doesn't have the start date, skipping...");
-                                continue;
-                            }
-                        };
-                        if date == Utc::now().date_naive() {
-                            sending_tasks.push(send_event_notification(
-                                discord_data.clone(),
-                                &sender_http,
-                                calendar,
-                                event,
-                            ));
-                        }
-                    }
-                    futures::future::join_all(sending_tasks).await;
+            }
+            _ = shutdown::wait_for_signal() => {
+                info!("Shutting down...");
+                shutdown.token.cancel();
+                shutdown.tracker.close();
+                if tokio::time::timeout(SHUTDOWN_TIMEOUT, shutdown.tracker.wait())
+                    .await
+                    .is_err()
+                {
+                    warn!("Some notifications or commands didn't finish in time");
+                }
+                shard_manager.shutdown_all().await;
+                if let Err(why) = discord_task.await {
+                    error!(?why, "The Discord client has panicked");
                 }
             }
-        });
-
-        tokio::select! {
-            _ = discord_task => (),
-            _ = calendar_task => (),
         };
 
+        pool.close().await;
+        info!("Bye!");
         Ok(())
     }
 }
-
-#[instrument(skip(data))]
-async fn send_event_notification(
-    data: Arc<RwLock<TypeMap>>,
-    sender_http: &Http,
-    calendar: &CalendarListEntry,
-    event: Event,
-) -> Result<(), Error> {
-    let lock = data.read().await;
-    let summary = calendar.summary.as_ref().expect("No calendar summary");
-    let guild_id = summary
-        .parse()
-        .map_err(|_| Error::CalendarSummaryNotDiscordServerId(summary.into()))?;
-    let guild_id = GuildId::new(guild_id);
-    let pool = lock.get::<Pool>().ok_or(Error::NoPool)?;
-    let Some(channel_id) = discord::get_event_channel_id(pool, &guild_id).await? else {
-        warn!(?guild_id, "The server has no event channel");
-        return Err(Error::DiscordSeverHasNoEventChannel(guild_id));
-    };
-    let message = CreateMessage::new().content(format!(
-        "Today is {}, have a nice celebration!🎉",
-        match event.summary.as_ref() {
-            Some(summary) => summary.as_str(),
-            None => "No label",
-        }
-    ));
-    sender_http
-        .send_message(channel_id, vec![], &message)
-        .await?;
-    Ok(())
-}
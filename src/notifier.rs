@@ -0,0 +1,584 @@
+use std::{sync::Arc, time::Duration};
+
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, Utc};
+use chrono_tz::Tz;
+use futures::StreamExt;
+use google_calendar3::api::Event;
+use serenity::{
+    all::{
+        Colour, CreateAllowedMentions, CreateEmbed, CreateMessage, GuildId, Http, Mentionable,
+        RoleId, Timestamp, UserId,
+    },
+    prelude::{RwLock, TypeMap},
+};
+use sqlx::PgPool;
+use tracing::{error, instrument, warn};
+
+use crate::{
+    calendar::{
+        self, event_duration, event_url, format_occurrence, format_recurrence, get_event_property,
+        occurrence_last_date, occurrences_between, CalendarBackend, EventStart, CATEGORY_PROPERTY,
+        IMAGE_URL_PROPERTY, MENTION_ROLES_PROPERTY, MENTION_USERS_PROPERTY,
+    },
+    dates::DateFormat,
+    discord,
+    shutdown::Shutdown,
+    text::shorten,
+    Error, Pool,
+};
+
+mod reminders;
+pub use reminders::*;
+mod sent_notifications;
+pub use sent_notifications::NotificationKind;
+use sent_notifications::*;
+mod style;
+pub use style::*;
+mod templates;
+pub use templates::*;
+
+/// Discord rejects the longer message contents and embed parts.
+const MAX_CONTENT_LENGTH: usize = 2000;
+const MAX_EMBED_TITLE_LENGTH: usize = 256;
+const MAX_EMBED_DESCRIPTION_LENGTH: usize = 4096;
+const MAX_EMBED_FIELD_LENGTH: usize = 1024;
+/// The title, description and fields of an embed together.
+const MAX_EMBED_LENGTH: usize = 6000;
+
+/// Polls the guild calendars and announces the events in their event channels.
+pub struct Notifier {
+    pub backend: Arc<dyn CalendarBackend>,
+    pub pool: PgPool,
+    pub data: Arc<RwLock<TypeMap>>,
+    pub http: Arc<Http>,
+    pub period: Duration,
+    pub shutdown: Shutdown,
+}
+
+impl Notifier {
+    pub async fn run(&self) -> Result<(), Error> {
+        let period = chrono::Duration::from_std(self.period).expect("Too long notification period");
+        let mut window_start = Utc::now();
+        loop {
+            // A restarted notifier doesn't poll again once the shutdown is requested
+            if self.shutdown.is_requested() {
+                return Ok(());
+            }
+            // The windows follow each other, so a timed event is never scheduled twice
+            let window_end = Utc::now() + period;
+            {
+                // The notifications being sent are finished before the shutdown
+                let _running = self.shutdown.tracker.token();
+                self.notify(window_start, window_end).await?;
+            }
+            window_start = window_end;
+
+            tokio::select! {
+                _ = self.shutdown.token.cancelled() => return Ok(()),
+                _ = tokio::time::sleep(self.period) => (),
+            }
+        }
+    }
+
+    /// Announces the all-day events happening today in the server time zone and reminds about
+    /// the upcoming ones, the timed events starting within the window are scheduled to be
+    /// announced at their start.
+    async fn notify(
+        &self,
+        window_start: DateTime<Utc>,
+        window_end: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        let calendars = calendar::list_guild_calendars(&self.pool).await?;
+
+        let mut calendars_handles = vec![];
+        for (guild_id, calendar_id) in &calendars {
+            let handle = self.backend.list_events(calendar_id);
+            calendars_handles.push((guild_id, calendar_id, handle));
+        }
+
+        let mut stream = futures::stream::iter(calendars_handles);
+        while let Some(handle) = stream.next().await {
+            let (guild_id, calendar_id, events) = handle;
+            let events = match events.await {
+                Ok(events) => events,
+                Err(why) => {
+                    error!(
+                        ?why,
+                        ?guild_id,
+                        "Couldn't list the events, skipping the server..."
+                    );
+                    continue;
+                }
+            };
+
+            let time_zone = discord::get_guild_time_zone(&self.pool, guild_id).await?;
+            let guild_reminder_days =
+                discord::get_guild_reminder_days(&self.pool, guild_id).await?;
+            let date_format = discord::get_guild_date_format(&self.pool, guild_id).await?;
+            let style = discord::get_guild_notification_style(&self.pool, guild_id).await?;
+            let calendar_url = self.backend.calendar_url(calendar_id);
+            let templates = get_guild_templates(&self.pool, guild_id).await?;
+            let today = Utc::now().with_timezone(&time_zone).date_naive();
+            let day_start = EventStart::AllDay(today).date_time();
+
+            let mut sending_tasks = vec![];
+            for event in events {
+                let reminder_days =
+                    event_reminder_days(&event).unwrap_or_else(|| guild_reminder_days.clone());
+                let max_days = reminder_days.iter().copied().max().unwrap_or_default();
+                // All-day occurrences are dates expanded at midnight UTC, a spare day on both
+                // sides covers the timed ones in any time zone. The multi-day occurrences
+                // started earlier may still go on today.
+                let length = event_duration(&event).num_days();
+                let from = (day_start - ChronoDuration::days(1 + length)).min(window_start);
+                let to =
+                    (day_start + ChronoDuration::days(i64::from(max_days) + 2)).max(window_end);
+                let occurrences = match occurrences_between(&event, from, to) {
+                    Ok(occurrences) => occurrences,
+                    Err(why) => {
+                        warn!(
+                            ?why,
+                            ?event,
+                            "Couldn't get the event occurrences, skipping..."
+                        );
+                        continue;
+                    }
+                };
+                let category = get_event_property(&event, CATEGORY_PROPERTY);
+                for start in occurrences {
+                    let start_date = start.local_date(&time_zone);
+                    let last_date = occurrence_last_date(&event, start, &time_zone);
+                    let days = (start_date - today).num_days();
+                    let notification = |kind| Notification {
+                        guild_id: *guild_id,
+                        event: event.clone(),
+                        start,
+                        kind,
+                        time_zone,
+                        date_format,
+                        style,
+                        calendar_url: calendar_url.clone(),
+                        template: templates.get(kind, category).map(String::from),
+                    };
+                    match start {
+                        EventStart::Timed(date_time)
+                            if reminder_days.contains(&0)
+                                && window_start <= date_time
+                                && date_time < window_end =>
+                        {
+                            self.schedule(notification(NotificationKind::Start));
+                        }
+                        EventStart::AllDay(_) if days == 0 && reminder_days.contains(&0) => {
+                            sending_tasks.push(send_logged(
+                                self.data.clone(),
+                                self.http.clone(),
+                                notification(NotificationKind::Today),
+                            ));
+                        }
+                        _ if days > 0 && reminder_days.contains(&(days as u32)) => {
+                            sending_tasks.push(send_logged(
+                                self.data.clone(),
+                                self.http.clone(),
+                                notification(NotificationKind::Reminder(days as u32)),
+                            ));
+                        }
+                        _ if days < 0 && today <= last_date && reminder_days.contains(&0) => {
+                            let kind = NotificationKind::Ongoing {
+                                day: (today - start_date).num_days() as u32 + 1,
+                                days: (last_date - start_date).num_days() as u32 + 1,
+                            };
+                            sending_tasks.push(send_logged(
+                                self.data.clone(),
+                                self.http.clone(),
+                                notification(kind),
+                            ));
+                        }
+                        _ => (),
+                    }
+                }
+            }
+            futures::future::join_all(sending_tasks).await;
+        }
+        Ok(())
+    }
+
+    fn schedule(&self, notification: Notification) {
+        let data = self.data.clone();
+        let http = self.http.clone();
+        let shutdown = self.shutdown.token.clone();
+        self.shutdown.tracker.spawn(async move {
+            let delay = (notification.start.date_time() - Utc::now())
+                .to_std()
+                .unwrap_or_default();
+            tokio::select! {
+                // Not due yet, the next start schedules it again
+                _ = shutdown.cancelled() => return,
+                _ = tokio::time::sleep(delay) => (),
+            }
+            send_logged(data, http, notification).await;
+        });
+    }
+}
+
+/// A notification about an event occurrence.
+#[derive(Debug)]
+struct Notification {
+    guild_id: GuildId,
+    event: Event,
+    start: EventStart,
+    kind: NotificationKind,
+    /// The server time zone and date format the occurrence is shown in.
+    time_zone: Tz,
+    date_format: DateFormat,
+    style: NotificationStyle,
+    calendar_url: Option<String>,
+    /// The custom template of the server, the default text is used without one.
+    template: Option<String>,
+}
+
+impl Notification {
+    fn label(&self) -> &str {
+        match self.event.summary.as_ref() {
+            Some(summary) => summary.as_str(),
+            None => "No label",
+        }
+    }
+
+    /// How many days the occurrence lasts.
+    fn days(&self) -> u32 {
+        let last_date = occurrence_last_date(&self.event, self.start, &self.time_zone);
+        (last_date - self.start.local_date(&self.time_zone)).num_days() as u32 + 1
+    }
+
+    /// The occurrence date, with the last one for the multi-day events.
+    fn dates(&self) -> String {
+        format_occurrence(&self.event, self.start, &self.time_zone, self.date_format)
+    }
+
+    fn content(&self) -> String {
+        if let Some(template) = self.template.as_deref() {
+            match render_template(template, &self.template_values()) {
+                Ok(content) => return content,
+                Err(why) => warn!(?why, "Couldn't render the template, using the default one"),
+            }
+        }
+
+        let label = self.label();
+        let when = self.dates();
+        match self.kind {
+            NotificationKind::Today if self.days() > 1 => {
+                format!("{label} starts today and lasts {} days!🎉", self.days())
+            }
+            NotificationKind::Today => format!("Today is {label}, have a nice celebration!🎉"),
+            NotificationKind::Start => format!("{label} is starting now!⏰"),
+            NotificationKind::Reminder(1) => format!("{label} is tomorrow ({when})!"),
+            NotificationKind::Reminder(days) => format!("{label} is in {days} days ({when})!"),
+            NotificationKind::Ongoing { day, days } => {
+                format!("{label} goes on, it's day {day} of {days}!")
+            }
+        }
+    }
+
+    fn template_values(&self) -> TemplateValues<'_> {
+        let date = self.start.local_date(&self.time_zone);
+        let first_date = EventStart::from_event(&self.event)
+            .map(|start| start.local_date(&self.time_zone))
+            .unwrap_or(date);
+        TemplateValues {
+            label: self.label(),
+            date: self.dates(),
+            days_until: match self.kind {
+                NotificationKind::Reminder(days) => days.into(),
+                NotificationKind::Today
+                | NotificationKind::Start
+                | NotificationKind::Ongoing { .. } => 0,
+            },
+            years: date.year() - first_date.year(),
+            calendar_url: self.calendar_url.clone(),
+            day: match self.kind {
+                NotificationKind::Ongoing { day, .. } => day,
+                _ => 1,
+            },
+            days: self.days(),
+        }
+    }
+
+    /// The text with the event description cut to fit `max` characters with the details.
+    fn with_description(
+        &self,
+        text: String,
+        separator: &str,
+        details: &[String],
+        max: usize,
+    ) -> String {
+        let used = [text.as_str()]
+            .into_iter()
+            .chain(details.iter().map(String::as_str))
+            .map(|part| part.chars().count() + separator.chars().count())
+            .sum::<usize>();
+        let budget = max.saturating_sub(used);
+        let description = self
+            .event
+            .description
+            .as_deref()
+            .filter(|_| budget > 1)
+            .map(|description| shorten(description, budget));
+        let text = [Some(text), description]
+            .into_iter()
+            .flatten()
+            .chain(details.iter().cloned())
+            .collect::<Vec<_>>()
+            .join(separator);
+        // The template and the details alone may be too long
+        shorten(&text, max)
+    }
+
+    /// The embed fields, every value cut to the Discord limit and the links too long for it
+    /// left out, as cutting them would break them.
+    fn embed_fields(&self) -> Vec<(&'static str, String, bool)> {
+        let values = [
+            ("Date", Some(self.dates())),
+            ("Repeats", format_recurrence(&self.event, self.date_format)),
+            ("Location", self.event.location.clone()),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| Some((name, shorten(&value?, MAX_EMBED_FIELD_LENGTH), true)));
+        let links = [
+            (
+                "Calendar",
+                self.calendar_url
+                    .as_deref()
+                    .map(|url| format!("[Open the calendar]({url})")),
+            ),
+            (
+                "Link",
+                event_url(&self.event).map(|url| format!("[Open the link]({url})")),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(name, link)| {
+            let link = link.filter(|link| link.chars().count() <= MAX_EMBED_FIELD_LENGTH)?;
+            Some((name, link, false))
+        });
+        values.chain(links).collect()
+    }
+
+    /// The embed title, description and fields, the description cut to what's left of the
+    /// embed length.
+    fn embed_parts(&self) -> (String, String, Vec<(&'static str, String, bool)>) {
+        let title = shorten(self.label(), MAX_EMBED_TITLE_LENGTH);
+        let fields = self.embed_fields();
+        let used = title.chars().count()
+            + fields
+                .iter()
+                .map(|(name, value, _)| name.chars().count() + value.chars().count())
+                .sum::<usize>();
+        let max = MAX_EMBED_LENGTH
+            .saturating_sub(used)
+            .min(MAX_EMBED_DESCRIPTION_LENGTH);
+        let description = self.with_description(self.content(), "\n\n", &[], max);
+        (title, description, fields)
+    }
+
+    fn embed(&self) -> CreateEmbed {
+        let (title, description, fields) = self.embed_parts();
+        let color = match self.kind {
+            NotificationKind::Today => Colour::GOLD,
+            NotificationKind::Start => Colour::DARK_GREEN,
+            NotificationKind::Reminder(_) => Colour::BLUE,
+            NotificationKind::Ongoing { .. } => Colour::ORANGE,
+        };
+        let mut embed = CreateEmbed::new()
+            .title(title)
+            .description(description)
+            .color(color)
+            .fields(fields);
+        // The title opens the event link over the calendar one
+        if let Some(url) = event_url(&self.event).or(self.calendar_url.as_deref()) {
+            embed = embed.url(url);
+        }
+        if let Some(image_url) = get_event_property(&self.event, IMAGE_URL_PROPERTY) {
+            embed = embed.thumbnail(image_url);
+        }
+        if let Ok(timestamp) = Timestamp::from_unix_timestamp(self.start.date_time().timestamp()) {
+            embed = embed.timestamp(timestamp);
+        }
+        embed
+    }
+
+    /// The template text followed by the event description, location and link, `max`
+    /// characters at most.
+    fn text(&self, max: usize) -> String {
+        let location = self
+            .event
+            .location
+            .as_deref()
+            .map(|location| format!("Location: {location}"));
+        let url = event_url(&self.event).map(|url| format!("Link: {url}"));
+        let details = [location, url].into_iter().flatten().collect::<Vec<_>>();
+        self.with_description(self.content(), "\n", &details, max)
+    }
+
+    /// The ids stored in the comma separated event property.
+    fn mention_ids<T: From<u64>>(&self, key: &str) -> Vec<T> {
+        get_event_property(&self.event, key)
+            .into_iter()
+            .flat_map(|ids| ids.split(','))
+            .filter_map(|id| id.trim().parse::<u64>().ok())
+            .filter(|id| *id != 0)
+            .map(T::from)
+            .collect()
+    }
+
+    /// Only the event roles and users may be pinged, never everyone, whatever the template says.
+    fn message(&self) -> CreateMessage {
+        let roles: Vec<RoleId> = self.mention_ids(MENTION_ROLES_PROPERTY);
+        let users: Vec<UserId> = self.mention_ids(MENTION_USERS_PROPERTY);
+        let mentions = roles
+            .iter()
+            .map(|role| role.mention().to_string())
+            .chain(users.iter().map(|user| user.mention().to_string()))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let allowed_mentions = CreateAllowedMentions::new()
+            .roles(roles)
+            .users(users)
+            .everyone(false);
+
+        // The embeds don't ping, so the mentions go to the content
+        let message = match self.style {
+            NotificationStyle::Embed => {
+                let message = CreateMessage::new().embed(self.embed());
+                if mentions.is_empty() {
+                    message
+                } else {
+                    message.content(mentions)
+                }
+            }
+            NotificationStyle::Text if mentions.is_empty() => {
+                CreateMessage::new().content(self.text(MAX_CONTENT_LENGTH))
+            }
+            NotificationStyle::Text => {
+                let max = MAX_CONTENT_LENGTH.saturating_sub(mentions.chars().count() + 1);
+                CreateMessage::new().content(format!("{mentions} {}", self.text(max)))
+            }
+        };
+        message.allowed_mentions(allowed_mentions)
+    }
+}
+
+/// Sends the notification, a failed one is logged and tried again on the next poll.
+async fn send_logged(
+    data: Arc<RwLock<TypeMap>>,
+    sender_http: Arc<Http>,
+    notification: Notification,
+) {
+    let guild_id = notification.guild_id;
+    let event_id = notification.event.id.clone();
+    let kind = notification.kind;
+    if let Err(why) = send_event_notification(data, sender_http, notification).await {
+        error!(
+            ?guild_id,
+            event_id,
+            ?kind,
+            ?why,
+            "Failed to send the notification"
+        );
+    }
+}
+
+#[instrument(skip(data, sender_http))]
+async fn send_event_notification(
+    data: Arc<RwLock<TypeMap>>,
+    sender_http: Arc<Http>,
+    notification: Notification,
+) -> Result<(), Error> {
+    let lock = data.read().await;
+    let pool = lock.get::<Pool>().ok_or(Error::NoPool)?;
+    let guild_id = notification.guild_id;
+
+    let Some(event_id) = notification.event.id.as_deref() else {
+        return Err(Error::MissingEventId);
+    };
+    let occurrence_date = notification.start.date_time().date_naive();
+    let kind = notification.kind;
+    if is_notification_sent(pool, &guild_id, event_id, occurrence_date, kind).await? {
+        return Ok(());
+    }
+
+    let Some(channel_id) = discord::get_event_channel_id(pool, &guild_id).await? else {
+        warn!(?guild_id, "The server has no event channel");
+        return Err(Error::DiscordSeverHasNoEventChannel(guild_id));
+    };
+    let message = notification.message();
+    sender_http
+        .send_message(channel_id, vec![], &message)
+        .await?;
+    record_sent_notification(pool, &guild_id, event_id, occurrence_date, kind).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use chrono_tz::Tz;
+    use google_calendar3::api::{Event, EventDateTime, EventSource};
+    use serenity::all::GuildId;
+
+    use super::{
+        EventStart, Notification, NotificationKind, NotificationStyle, MAX_EMBED_FIELD_LENGTH,
+        MAX_EMBED_LENGTH,
+    };
+
+    fn notification(event: Event, calendar_url: Option<String>) -> Notification {
+        let date = NaiveDate::from_ymd_opt(2026, 12, 25).unwrap();
+        Notification {
+            guild_id: GuildId::new(1),
+            event: Event {
+                start: Some(EventDateTime {
+                    date: Some(date),
+                    ..Default::default()
+                }),
+                ..event
+            },
+            start: EventStart::AllDay(date),
+            kind: NotificationKind::Today,
+            time_zone: Tz::UTC,
+            date_format: Default::default(),
+            style: NotificationStyle::Embed,
+            calendar_url,
+            template: None,
+        }
+    }
+
+    #[test]
+    fn fits_the_embed_in_the_discord_limits() {
+        let event = Event {
+            summary: Some("a".repeat(300)),
+            description: Some("b".repeat(5000)),
+            location: Some("c".repeat(2000)),
+            recurrence: Some(vec![format!("RRULE:{}", "d".repeat(2000))]),
+            source: Some(EventSource {
+                title: None,
+                url: Some(format!("https://example.com/{}", "e".repeat(980))),
+            }),
+            ..Default::default()
+        };
+        let calendar_url = format!("https://example.com/{}", "f".repeat(2000));
+        let (title, description, fields) = notification(event, Some(calendar_url)).embed_parts();
+
+        assert!(fields
+            .iter()
+            .all(|(_, value, _)| value.chars().count() <= MAX_EMBED_FIELD_LENGTH));
+        let names = fields.iter().map(|(name, _, _)| *name).collect::<Vec<_>>();
+        assert_eq!(names, ["Date", "Repeats", "Location", "Link"]);
+        let length = title.chars().count()
+            + description.chars().count()
+            + fields
+                .iter()
+                .map(|(name, value, _)| name.chars().count() + value.chars().count())
+                .sum::<usize>();
+        assert!(length <= MAX_EMBED_LENGTH);
+        assert!(description.ends_with('…'));
+    }
+}
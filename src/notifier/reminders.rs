@@ -0,0 +1,68 @@
+use google_calendar3::api::Event;
+
+use crate::{
+    calendar::{get_event_property, REMINDER_DAYS_PROPERTY},
+    Error,
+};
+
+/// Reminders can't be set further than a year ahead.
+const MAX_REMINDER_DAYS: u32 = 365;
+
+/// Parses comma separated days before an occurrence, `0` being the day itself.
+pub fn parse_reminder_days(days: &str) -> Result<Vec<u32>, Error> {
+    let mut reminder_days = days
+        .split(',')
+        .map(|day| {
+            day.trim()
+                .parse::<u32>()
+                .ok()
+                .filter(|day| *day <= MAX_REMINDER_DAYS)
+                .ok_or_else(|| Error::InvalidReminderDays(days.into()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    reminder_days.sort_unstable_by(|a, b| b.cmp(a));
+    reminder_days.dedup();
+    Ok(reminder_days)
+}
+
+pub fn format_reminder_days(days: &[u32]) -> String {
+    days.iter()
+        .map(u32::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// The reminders set for the event, overriding the server ones.
+pub fn event_reminder_days(event: &Event) -> Option<Vec<u32>> {
+    parse_reminder_days(get_event_property(event, REMINDER_DAYS_PROPERTY)?).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_reminder_days, parse_reminder_days};
+
+    #[test]
+    fn sorts_and_dedups_the_days() {
+        assert_eq!(parse_reminder_days("1, 7,0,7").unwrap(), vec![7, 1, 0]);
+    }
+
+    #[test]
+    fn rejects_invalid_days() {
+        assert!(parse_reminder_days("").is_err());
+        assert!(parse_reminder_days("7,,1").is_err());
+        assert!(parse_reminder_days("-1").is_err());
+        assert!(parse_reminder_days("week").is_err());
+        assert!(parse_reminder_days("366").is_err());
+    }
+
+    #[test]
+    fn accepts_a_year_ahead() {
+        assert_eq!(parse_reminder_days("365").unwrap(), vec![365]);
+    }
+
+    #[test]
+    fn formats_back_to_the_input() {
+        let days = parse_reminder_days("7,1,0").unwrap();
+        assert_eq!(format_reminder_days(&days), "7,1,0");
+    }
+}
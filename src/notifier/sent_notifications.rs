@@ -0,0 +1,85 @@
+use chrono::NaiveDate;
+use serenity::all::GuildId;
+use sqlx::{query, PgPool};
+
+use crate::Error;
+
+/// Which notification of an event occurrence is sent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NotificationKind {
+    /// An all-day event starts today.
+    Today,
+    /// A timed event is starting.
+    Start,
+    /// The event is in the given number of days.
+    Reminder(u32),
+    /// A multi-day event goes on, on its `day` of `days`.
+    Ongoing { day: u32, days: u32 },
+}
+
+impl NotificationKind {
+    pub fn key(&self) -> String {
+        match self {
+            Self::Today => "today".into(),
+            Self::Start => "start".into(),
+            Self::Reminder(days) => format!("reminder:{days}"),
+            Self::Ongoing { day, .. } => format!("ongoing:{day}"),
+        }
+    }
+
+    /// The notification templates are shared by all the reminders.
+    pub fn template_key(&self) -> &'static str {
+        match self {
+            Self::Today => "today",
+            Self::Start => "start",
+            Self::Reminder(_) => "reminder",
+            Self::Ongoing { .. } => "ongoing",
+        }
+    }
+}
+
+pub async fn is_notification_sent(
+    pool: &PgPool,
+    guild_id: &GuildId,
+    event_id: &str,
+    occurrence_date: NaiveDate,
+    kind: NotificationKind,
+) -> Result<bool, Error> {
+    let response = query!(
+        "
+        SELECT sent_at FROM sent_notifications
+        WHERE guild_id = $1 AND event_id = $2 AND occurrence_date = $3 AND kind = $4
+        ",
+        guild_id.get().to_string(),
+        event_id,
+        occurrence_date,
+        kind.key(),
+    )
+    .fetch_optional(pool)
+    .await?
+    .is_some();
+    Ok(response)
+}
+
+pub async fn record_sent_notification(
+    pool: &PgPool,
+    guild_id: &GuildId,
+    event_id: &str,
+    occurrence_date: NaiveDate,
+    kind: NotificationKind,
+) -> Result<(), Error> {
+    query!(
+        "
+        INSERT INTO sent_notifications(guild_id, event_id, occurrence_date, kind)
+        VALUES($1, $2, $3, $4)
+        ON CONFLICT DO NOTHING
+        ",
+        guild_id.get().to_string(),
+        event_id,
+        occurrence_date,
+        kind.key(),
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
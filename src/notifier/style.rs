@@ -0,0 +1,26 @@
+use crate::Error;
+
+/// How the notifications are posted in the event channel.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NotificationStyle {
+    #[default]
+    Embed,
+    Text,
+}
+
+impl NotificationStyle {
+    pub fn parse(style: &str) -> Result<Self, Error> {
+        match style {
+            "embed" => Ok(Self::Embed),
+            "text" => Ok(Self::Text),
+            style => Err(Error::InvalidNotificationStyle(style.into())),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Embed => "embed",
+            Self::Text => "text",
+        }
+    }
+}
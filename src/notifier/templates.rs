@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+
+use serenity::all::GuildId;
+use sqlx::{query, PgPool};
+
+use crate::Error;
+
+use super::NotificationKind;
+
+/// The placeholders a notification template may use.
+pub const TEMPLATE_PLACEHOLDERS: [&str; 7] = [
+    "label",
+    "date",
+    "days_until",
+    "years",
+    "calendar_url",
+    "day",
+    "days",
+];
+/// Leaves room for the event details in a Discord message.
+pub const MAX_TEMPLATE_LENGTH: u16 = 1000;
+
+/// The values substituted for the template placeholders.
+#[derive(Debug)]
+pub struct TemplateValues<'a> {
+    pub label: &'a str,
+    pub date: String,
+    pub days_until: i64,
+    /// How many years ago the event first happened, e.g. the age on a birthday.
+    pub years: i32,
+    pub calendar_url: Option<String>,
+    /// The day of a multi-day event it's on, 1 before it goes on.
+    pub day: u32,
+    /// How many days the event lasts.
+    pub days: u32,
+}
+
+impl TemplateValues<'_> {
+    fn get(&self, placeholder: &str) -> Option<String> {
+        match placeholder {
+            "label" => Some(self.label.into()),
+            "date" => Some(self.date.clone()),
+            "days_until" => Some(self.days_until.to_string()),
+            "years" => Some(self.years.to_string()),
+            "calendar_url" => Some(self.calendar_url.clone().unwrap_or_default()),
+            "day" => Some(self.day.to_string()),
+            "days" => Some(self.days.to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// The placeholders as they're written in a template, e.g. `{label}, {date}`.
+pub fn format_placeholders() -> String {
+    TEMPLATE_PLACEHOLDERS
+        .map(|name| format!("{{{name}}}"))
+        .join(", ")
+}
+
+/// Substitutes the `{placeholder}`s of the template, failing on unknown ones and unbalanced
+/// braces, so rendering it with any values validates it.
+pub fn render_template(template: &str, values: &TemplateValues) -> Result<String, Error> {
+    if template.trim().is_empty() || template.chars().count() > MAX_TEMPLATE_LENGTH.into() {
+        return Err(Error::InvalidTemplate(format!(
+            "the template must have 1 to {MAX_TEMPLATE_LENGTH} characters"
+        )));
+    }
+
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(open) = rest.find(['{', '}']) {
+        rendered.push_str(&rest[..open]);
+        if rest[open..].starts_with('}') {
+            return Err(Error::InvalidTemplate("unexpected }".into()));
+        }
+        let Some(close) = rest[open..].find('}') else {
+            return Err(Error::InvalidTemplate("unclosed {".into()));
+        };
+        let placeholder = &rest[open + 1..open + close];
+        let Some(value) = values.get(placeholder) else {
+            return Err(Error::InvalidTemplate(format!(
+                "unknown placeholder {{{placeholder}}}, use {}",
+                format_placeholders()
+            )));
+        };
+        rendered.push_str(&value);
+        rest = &rest[open + close + 1..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+/// The custom templates of a server by the notification kind and the event category.
+#[derive(Debug, Default)]
+pub struct GuildTemplates(HashMap<(String, String), String>);
+
+impl GuildTemplates {
+    /// The template of the event category, or the server one for any category.
+    pub fn get(&self, kind: NotificationKind, category: Option<&str>) -> Option<&str> {
+        let kind = kind.template_key();
+        category
+            .and_then(|category| self.0.get(&(kind.into(), category.into())))
+            .or_else(|| self.0.get(&(kind.into(), String::new())))
+            .map(String::as_str)
+    }
+}
+
+pub async fn get_guild_templates(
+    pool: &PgPool,
+    guild_id: &GuildId,
+) -> Result<GuildTemplates, Error> {
+    let templates = query!(
+        "
+        SELECT kind, category, template FROM notification_templates
+        WHERE guild_id = $1
+        ",
+        guild_id.get().to_string()
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|record| ((record.kind, record.category), record.template))
+    .collect();
+    Ok(GuildTemplates(templates))
+}
+
+/// Stores the template, an empty category makes it the server one.
+pub async fn set_notification_template(
+    pool: &PgPool,
+    guild_id: &GuildId,
+    kind: &str,
+    category: &str,
+    template: &str,
+) -> Result<(), Error> {
+    query!(
+        "
+        INSERT INTO notification_templates(guild_id, kind, category, template)
+        VALUES($1, $2, $3, $4)
+        ON CONFLICT (guild_id, kind, category) DO UPDATE SET template = EXCLUDED.template
+        ",
+        guild_id.get().to_string(),
+        kind,
+        category,
+        template,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn delete_notification_template(
+    pool: &PgPool,
+    guild_id: &GuildId,
+    kind: &str,
+    category: &str,
+) -> Result<(), Error> {
+    query!(
+        "
+        DELETE FROM notification_templates
+        WHERE guild_id = $1 AND kind = $2 AND category = $3
+        ",
+        guild_id.get().to_string(),
+        kind,
+        category,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render_template, TemplateValues};
+
+    fn values() -> TemplateValues<'static> {
+        TemplateValues {
+            label: "Alice's birthday",
+            date: "2026-12-25".into(),
+            days_until: 3,
+            years: 30,
+            calendar_url: None,
+            day: 2,
+            days: 3,
+        }
+    }
+
+    #[test]
+    fn substitutes_the_placeholders() {
+        let rendered = render_template(
+            "{label} on {date}, in {days_until} days, {years} years, day {day}/{days}{calendar_url}",
+            &values(),
+        )
+        .unwrap();
+        assert_eq!(
+            rendered,
+            "Alice's birthday on 2026-12-25, in 3 days, 30 years, day 2/3"
+        );
+    }
+
+    #[test]
+    fn keeps_the_text_without_placeholders() {
+        assert_eq!(render_template("Party!", &values()).unwrap(), "Party!");
+    }
+
+    #[test]
+    fn rejects_unknown_placeholders() {
+        let why = render_template("{name} is here", &values()).unwrap_err();
+        assert!(why.to_string().contains("{name}"));
+    }
+
+    #[test]
+    fn rejects_unbalanced_braces() {
+        assert!(render_template("{label", &values()).is_err());
+        assert!(render_template("label}", &values()).is_err());
+    }
+
+    #[test]
+    fn rejects_empty_and_long_templates() {
+        assert!(render_template(" ", &values()).is_err());
+        assert!(render_template(&"a".repeat(1001), &values()).is_err());
+    }
+
+    #[test]
+    fn counts_the_characters_not_bytes() {
+        assert!(render_template(&"🎉".repeat(1000), &values()).is_ok());
+        assert!(render_template(&"🎉".repeat(1001), &values()).is_err());
+    }
+}
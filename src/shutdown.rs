@@ -0,0 +1,40 @@
+use serenity::prelude::TypeMapKey;
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
+use tracing::info;
+
+/// Signals the shutdown and tracks the work that has to finish before it, like the
+/// notifications being sent and the interactions being handled.
+#[derive(Clone, Default)]
+pub struct Shutdown {
+    pub token: CancellationToken,
+    pub tracker: TaskTracker,
+}
+
+impl TypeMapKey for Shutdown {
+    type Value = Shutdown;
+}
+
+impl Shutdown {
+    pub fn is_requested(&self) -> bool {
+        self.token.is_cancelled()
+    }
+}
+
+/// Waits for SIGINT, or SIGTERM on Unix where the orchestrators stop the containers with it.
+pub async fn wait_for_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut terminate = signal(SignalKind::terminate()).expect("Failed to listen to SIGTERM");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => info!("Received SIGINT"),
+            _ = terminate.recv() => info!("Received SIGTERM"),
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+        info!("Received Ctrl-C");
+    }
+}
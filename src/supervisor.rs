@@ -0,0 +1,137 @@
+use std::{collections::BTreeMap, fmt, future::Future, sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use serenity::prelude::{RwLock, TypeMapKey};
+use tokio::{task::JoinHandle, time::Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use crate::Error;
+
+/// The delay before the first restart, doubled on every next failure in a row.
+const BASE_RESTART_DELAY: Duration = Duration::from_secs(1);
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(5 * 60);
+/// A task running this long is considered recovered, its failures are forgotten.
+const RECOVERY_PERIOD: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Clone, Debug)]
+pub enum TaskStatus {
+    Running { since: DateTime<Utc> },
+    Restarting { failures: u32, error: String },
+    Finished,
+}
+
+impl fmt::Display for TaskStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Running { since } => {
+                write!(f, "running since {}", since.format("%Y-%m-%d %H:%M %Z"))
+            }
+            Self::Restarting { failures, error } => {
+                write!(
+                    f,
+                    "restarting after {failures} failures, last error: {error}"
+                )
+            }
+            Self::Finished => write!(f, "finished"),
+        }
+    }
+}
+
+/// Restarts the failed background tasks and keeps track of their health.
+#[derive(Clone, Default)]
+pub struct Supervisor {
+    health: Arc<RwLock<BTreeMap<&'static str, TaskStatus>>>,
+    /// No task is restarted once the shutdown is requested.
+    shutdown: CancellationToken,
+}
+
+pub struct Tasks;
+
+impl TypeMapKey for Tasks {
+    type Value = Supervisor;
+}
+
+impl Supervisor {
+    pub fn new(shutdown: CancellationToken) -> Self {
+        Self {
+            health: Default::default(),
+            shutdown,
+        }
+    }
+
+    pub async fn health(&self) -> Vec<(&'static str, TaskStatus)> {
+        let health = self.health.read().await;
+        health
+            .iter()
+            .map(|(name, status)| (*name, status.clone()))
+            .collect()
+    }
+
+    async fn set_status(&self, name: &'static str, status: TaskStatus) {
+        self.health.write().await.insert(name, status);
+    }
+
+    /// Runs the task built by `task` until it finishes, a failed or panicked one is logged and
+    /// started again with backoff.
+    pub fn spawn<F, Fut>(&self, name: &'static str, task: F) -> JoinHandle<()>
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<(), Error>> + Send + 'static,
+    {
+        let supervisor = self.clone();
+        tokio::spawn(async move {
+            let mut failures = 0;
+            loop {
+                let since = Utc::now();
+                supervisor
+                    .set_status(name, TaskStatus::Running { since })
+                    .await;
+                let started = Instant::now();
+
+                // Spawned on its own, so a panic is caught as well
+                let why = match tokio::spawn(task()).await {
+                    Ok(Ok(())) => {
+                        info!(name, "The task has finished");
+                        supervisor.set_status(name, TaskStatus::Finished).await;
+                        return;
+                    }
+                    Ok(Err(why)) => why.to_string(),
+                    Err(why) => why.to_string(),
+                };
+                error!(name, why, "The task has failed");
+                if supervisor.shutdown.is_cancelled() {
+                    info!(name, "Shutting down, the task isn't restarted");
+                    supervisor.set_status(name, TaskStatus::Finished).await;
+                    return;
+                }
+
+                if started.elapsed() >= RECOVERY_PERIOD {
+                    failures = 0;
+                }
+                failures += 1;
+                let delay = BASE_RESTART_DELAY
+                    .saturating_mul(2u32.saturating_pow(failures - 1))
+                    .min(MAX_RESTART_DELAY);
+                warn!(name, failures, ?delay, "Restarting the task...");
+                supervisor
+                    .set_status(
+                        name,
+                        TaskStatus::Restarting {
+                            failures,
+                            error: why,
+                        },
+                    )
+                    .await;
+                tokio::select! {
+                    _ = supervisor.shutdown.cancelled() => {
+                        info!(name, "Shutting down, the task isn't restarted");
+                        supervisor.set_status(name, TaskStatus::Finished).await;
+                        return;
+                    }
+                    _ = tokio::time::sleep(delay) => (),
+                }
+            }
+        })
+    }
+}
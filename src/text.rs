@@ -0,0 +1,19 @@
+/// The text cut to `max` characters, the cut marked with `…`.
+pub fn shorten(text: &str, max: usize) -> String {
+    match text.char_indices().nth(max.saturating_sub(1)) {
+        Some((end, _)) if text.chars().count() > max => format!("{}…", &text[..end]),
+        _ => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::shorten;
+
+    #[test]
+    fn shortens_by_characters() {
+        assert_eq!(shorten("Été à Paris", 5), "Été …");
+        assert_eq!(shorten("Paris", 5), "Paris");
+        assert_eq!(shorten("Paris!", 5), "Pari…");
+    }
+}